@@ -0,0 +1,382 @@
+/// Looks up the emoji for a `:shortcode:` name, using the names Discord and GitHub share.
+pub fn shortcode(name: &str) -> Option<&'static str> {
+    Some(match name {
+        // Faces
+        "grinning" => "😀",
+        "smiley" => "😃",
+        "smile" => "😄",
+        "grin" => "😁",
+        "laughing" | "satisfied" => "😆",
+        "sweat_smile" => "😅",
+        "rofl" | "rolling_on_the_floor_laughing" => "🤣",
+        "joy" => "😂",
+        "slight_smile" | "slightly_smiling_face" => "🙂",
+        "upside_down" | "upside_down_face" => "🙃",
+        "wink" => "😉",
+        "blush" => "😊",
+        "innocent" => "😇",
+        "smiling_face_with_3_hearts" => "🥰",
+        "heart_eyes" => "😍",
+        "star_struck" => "🤩",
+        "kissing_heart" => "😘",
+        "kissing" => "😗",
+        "yum" => "😋",
+        "stuck_out_tongue" => "😛",
+        "stuck_out_tongue_winking_eye" => "😜",
+        "zany_face" => "🤪",
+        "stuck_out_tongue_closed_eyes" => "😝",
+        "money_mouth" | "money_mouth_face" => "🤑",
+        "hugging" | "hugs" => "🤗",
+        "thinking" | "thinking_face" => "🤔",
+        "zipper_mouth" | "zipper_mouth_face" => "🤐",
+        "raised_eyebrow" => "🤨",
+        "neutral_face" => "😐",
+        "expressionless" => "😑",
+        "no_mouth" => "😶",
+        "smirk" => "😏",
+        "unamused" => "😒",
+        "rolling_eyes" | "roll_eyes" => "🙄",
+        "grimacing" => "😬",
+        "relieved" => "😌",
+        "pensive" => "😔",
+        "sleepy" => "😪",
+        "drooling_face" => "🤤",
+        "sleeping" => "😴",
+        "mask" => "😷",
+        "nauseated_face" => "🤢",
+        "vomiting_face" => "🤮",
+        "sneezing_face" => "🤧",
+        "hot_face" => "🥵",
+        "cold_face" => "🥶",
+        "woozy_face" => "🥴",
+        "dizzy_face" => "😵",
+        "exploding_head" => "🤯",
+        "cowboy" | "cowboy_hat_face" => "🤠",
+        "partying_face" => "🥳",
+        "sunglasses" => "😎",
+        "nerd" | "nerd_face" => "🤓",
+        "confused" => "😕",
+        "worried" => "😟",
+        "slight_frown" | "slightly_frowning_face" => "🙁",
+        "frowning2" | "frowning_face" => "☹️",
+        "open_mouth" => "😮",
+        "hushed" => "😯",
+        "astonished" => "😲",
+        "flushed" => "😳",
+        "pleading_face" => "🥺",
+        "frowning" => "😦",
+        "anguished" => "😧",
+        "fearful" => "😨",
+        "cold_sweat" => "😰",
+        "disappointed_relieved" => "😥",
+        "cry" => "😢",
+        "sob" => "😭",
+        "scream" => "😱",
+        "confounded" => "😖",
+        "persevere" => "😣",
+        "disappointed" => "😞",
+        "sweat" => "😓",
+        "weary" => "😩",
+        "tired_face" => "😫",
+        "yawning_face" => "🥱",
+        "triumph" => "😤",
+        "rage" | "pout" => "😡",
+        "angry" => "😠",
+        "face_with_symbols_over_mouth" | "cursing_face" => "🤬",
+        "smiling_imp" => "😈",
+        "imp" => "👿",
+        "skull" => "💀",
+        "poop" | "hankey" | "shit" => "💩",
+        "clown" | "clown_face" => "🤡",
+        "ghost" => "👻",
+        "alien" => "👽",
+        "robot" => "🤖",
+        "smiley_cat" => "😺",
+        "see_no_evil" => "🙈",
+        "hear_no_evil" => "🙉",
+        "speak_no_evil" => "🙊",
+        "eyes" => "👀",
+        "eye" => "👁️",
+        "brain" => "🧠",
+        // Hands and people
+        "wave" => "👋",
+        "raised_hand" | "hand" => "✋",
+        "ok_hand" => "👌",
+        "pinching_hand" => "🤏",
+        "v" => "✌️",
+        "crossed_fingers" | "fingers_crossed" => "🤞",
+        "metal" => "🤘",
+        "call_me" | "call_me_hand" => "🤙",
+        "point_left" => "👈",
+        "point_right" => "👉",
+        "point_up_2" => "👆",
+        "point_down" => "👇",
+        "point_up" => "☝️",
+        "thumbsup" | "+1" | "thumbup" => "👍",
+        "thumbsdown" | "-1" | "thumbdown" => "👎",
+        "fist" => "✊",
+        "punch" | "facepunch" => "👊",
+        "clap" => "👏",
+        "raised_hands" => "🙌",
+        "open_hands" => "👐",
+        "handshake" => "🤝",
+        "pray" => "🙏",
+        "writing_hand" => "✍️",
+        "nail_care" => "💅",
+        "muscle" => "💪",
+        "man_shrugging" => "🤷‍♂️",
+        "woman_shrugging" => "🤷‍♀️",
+        "person_shrugging" | "shrug" => "🤷",
+        "person_facepalming" | "facepalm" => "🤦",
+        "baby" => "👶",
+        "ninja" => "🥷",
+        // Hearts and symbols
+        "heart" => "❤️",
+        "orange_heart" => "🧡",
+        "yellow_heart" => "💛",
+        "green_heart" => "💚",
+        "blue_heart" => "💙",
+        "purple_heart" => "💜",
+        "black_heart" => "🖤",
+        "white_heart" => "🤍",
+        "brown_heart" => "🤎",
+        "broken_heart" => "💔",
+        "two_hearts" => "💕",
+        "revolving_hearts" => "💞",
+        "heartbeat" => "💓",
+        "heartpulse" => "💗",
+        "sparkling_heart" => "💖",
+        "cupid" => "💘",
+        "gift_heart" => "💝",
+        "kiss" => "💋",
+        "100" => "💯",
+        "anger" => "💢",
+        "boom" | "collision" => "💥",
+        "dizzy" => "💫",
+        "sweat_drops" => "💦",
+        "dash" => "💨",
+        "zzz" => "💤",
+        "speech_balloon" => "💬",
+        "thought_balloon" => "💭",
+        "fire" | "flame" => "🔥",
+        "sparkles" => "✨",
+        "star" => "⭐",
+        "star2" => "🌟",
+        "tada" => "🎉",
+        "confetti_ball" => "🎊",
+        "balloon" => "🎈",
+        "gift" => "🎁",
+        "trophy" => "🏆",
+        "medal" | "sports_medal" => "🏅",
+        "crown" => "👑",
+        "gem" => "💎",
+        "bell" => "🔔",
+        "musical_note" => "🎵",
+        "notes" => "🎶",
+        "warning" => "⚠️",
+        "no_entry" => "⛔",
+        "x" => "❌",
+        "o" => "⭕",
+        "white_check_mark" => "✅",
+        "heavy_check_mark" => "✔️",
+        "ballot_box_with_check" => "☑️",
+        "question" => "❓",
+        "exclamation" | "heavy_exclamation_mark" => "❗",
+        "bangbang" => "‼️",
+        "interrobang" => "⁉️",
+        "heavy_plus_sign" => "➕",
+        "heavy_minus_sign" => "➖",
+        "arrow_right" => "➡️",
+        "arrow_left" => "⬅️",
+        "arrow_up" => "⬆️",
+        "arrow_down" => "⬇️",
+        "repeat" => "🔁",
+        "recycle" => "♻️",
+        "copyright" => "©️",
+        "registered" => "®️",
+        "tm" => "™️",
+        "infinity" => "♾️",
+        "zero" => "0️⃣",
+        "one" => "1️⃣",
+        "two" => "2️⃣",
+        "three" => "3️⃣",
+        "four" => "4️⃣",
+        "five" => "5️⃣",
+        "six" => "6️⃣",
+        "seven" => "7️⃣",
+        "eight" => "8️⃣",
+        "nine" => "9️⃣",
+        "keycap_ten" => "🔟",
+        "red_circle" => "🔴",
+        "green_circle" => "🟢",
+        "blue_circle" => "🔵",
+        // Nature, food and things
+        "sunny" => "☀️",
+        "cloud" => "☁️",
+        "umbrella" => "☔",
+        "zap" => "⚡",
+        "snowflake" => "❄️",
+        "rainbow" => "🌈",
+        "crescent_moon" => "🌙",
+        "earth_africa" => "🌍",
+        "earth_americas" => "🌎",
+        "earth_asia" => "🌏",
+        "seedling" => "🌱",
+        "evergreen_tree" => "🌲",
+        "cactus" => "🌵",
+        "four_leaf_clover" => "🍀",
+        "rose" => "🌹",
+        "sunflower" => "🌻",
+        "cherry_blossom" => "🌸",
+        "dog" => "🐶",
+        "cat" => "🐱",
+        "mouse" => "🐭",
+        "rabbit" => "🐰",
+        "fox" | "fox_face" => "🦊",
+        "bear" => "🐻",
+        "panda_face" => "🐼",
+        "frog" => "🐸",
+        "monkey_face" => "🐵",
+        "chicken" => "🐔",
+        "penguin" => "🐧",
+        "bird" => "🐦",
+        "unicorn" | "unicorn_face" => "🦄",
+        "bee" | "honeybee" => "🐝",
+        "bug" => "🐛",
+        "butterfly" => "🦋",
+        "snake" => "🐍",
+        "turtle" => "🐢",
+        "octopus" => "🐙",
+        "fish" => "🐟",
+        "whale" => "🐳",
+        "crab" => "🦀",
+        "apple" => "🍎",
+        "green_apple" => "🍏",
+        "banana" => "🍌",
+        "watermelon" => "🍉",
+        "grapes" => "🍇",
+        "strawberry" => "🍓",
+        "peach" => "🍑",
+        "cherries" => "🍒",
+        "lemon" => "🍋",
+        "avocado" => "🥑",
+        "eggplant" => "🍆",
+        "hot_pepper" => "🌶️",
+        "bread" => "🍞",
+        "cheese" => "🧀",
+        "egg" => "🥚",
+        "bacon" => "🥓",
+        "hamburger" => "🍔",
+        "fries" => "🍟",
+        "pizza" => "🍕",
+        "hotdog" => "🌭",
+        "taco" => "🌮",
+        "burrito" => "🌯",
+        "popcorn" => "🍿",
+        "sushi" => "🍣",
+        "ramen" => "🍜",
+        "spaghetti" => "🍝",
+        "doughnut" => "🍩",
+        "cookie" => "🍪",
+        "cake" => "🍰",
+        "birthday" => "🎂",
+        "chocolate_bar" => "🍫",
+        "candy" => "🍬",
+        "ice_cream" => "🍨",
+        "coffee" => "☕",
+        "tea" => "🍵",
+        "beer" => "🍺",
+        "beers" => "🍻",
+        "wine_glass" => "🍷",
+        "cocktail" => "🍸",
+        "champagne" => "🍾",
+        "soccer" => "⚽",
+        "basketball" => "🏀",
+        "football" => "🏈",
+        "tennis" => "🎾",
+        "video_game" => "🎮",
+        "game_die" => "🎲",
+        "dart" => "🎯",
+        "rocket" => "🚀",
+        "airplane" => "✈️",
+        "car" | "red_car" => "🚗",
+        "bike" => "🚲",
+        "house" => "🏠",
+        "computer" => "💻",
+        "keyboard" => "⌨️",
+        "iphone" | "mobile_phone" => "📱",
+        "camera" => "📷",
+        "tv" => "📺",
+        "bulb" => "💡",
+        "battery" => "🔋",
+        "money_with_wings" => "💸",
+        "moneybag" => "💰",
+        "envelope" => "✉️",
+        "pencil" | "memo" => "📝",
+        "pencil2" => "✏️",
+        "book" | "open_book" => "📖",
+        "books" => "📚",
+        "link" => "🔗",
+        "paperclip" => "📎",
+        "pushpin" => "📌",
+        "scissors" => "✂️",
+        "lock" => "🔒",
+        "unlock" => "🔓",
+        "key" => "🔑",
+        "hammer" => "🔨",
+        "wrench" => "🔧",
+        "gear" => "⚙️",
+        "mag" => "🔍",
+        "hourglass" => "⌛",
+        "alarm_clock" => "⏰",
+        "calendar" => "📆",
+        "chart_with_upwards_trend" => "📈",
+        "chart_with_downwards_trend" => "📉",
+        "package" => "📦",
+        "pill" => "💊",
+        "bomb" => "💣",
+        "crystal_ball" => "🔮",
+        "white_flag" => "🏳️",
+        "checkered_flag" => "🏁",
+        "triangular_flag_on_post" => "🚩",
+        "rainbow_flag" => "🏳️‍🌈",
+        _ => return None,
+    })
+}
+
+/// Matches a classic emoticon like `:)` or `<3` at the start of `text`, returning the emoji and
+/// the length of the emoticon.
+pub fn emoticon(text: &str) -> Option<(&'static str, usize)> {
+    // Longer emoticons come first so `:-)` isn't read as `:-` followed by text.
+    const EMOTICONS: &[(&str, &str)] = &[
+        (":'-(", "😢"),
+        (":'(", "😢"),
+        (":-)", "🙂"),
+        (":-(", "🙁"),
+        (":-D", "😄"),
+        (":-P", "😛"),
+        (":-p", "😛"),
+        (";-)", "😉"),
+        (":-O", "😮"),
+        (":-o", "😮"),
+        (":-|", "😐"),
+        (":-*", "😘"),
+        ("</3", "💔"),
+        (":)", "🙂"),
+        (":(", "🙁"),
+        (":D", "😄"),
+        (":P", "😛"),
+        (":p", "😛"),
+        (";)", "😉"),
+        (":O", "😮"),
+        (":o", "😮"),
+        (":|", "😐"),
+        (":*", "😘"),
+        ("<3", "❤️"),
+    ];
+
+    EMOTICONS
+        .iter()
+        .find(|(emoticon, _)| text.starts_with(emoticon))
+        .map(|(emoticon, emoji)| (*emoji, emoticon.len()))
+}
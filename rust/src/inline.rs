@@ -0,0 +1,299 @@
+//! Chat specific inline syntax that pulldown-cmark doesn't know about. It is recognized inside
+//! text runs, so nothing in code spans or code blocks is touched.
+
+use crate::{emoji, timestamp, MentionKind};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DelimiterKind {
+    /// `==highlighted==`
+    Highlight,
+    /// `||spoiler||`
+    Spoiler,
+}
+
+impl DelimiterKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DelimiterKind::Highlight => "==",
+            DelimiterKind::Spoiler => "||",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inline<'a> {
+    Text(&'a str),
+    /// `<@id>`, `<#id>` or `<@&id>` with the raw id. `raw` is the whole token.
+    Mention {
+        kind: MentionKind,
+        id: &'a str,
+        raw: &'a str,
+    },
+    /// `@username`, only a mention when the name is known.
+    Username {
+        name: &'a str,
+        raw: &'a str,
+    },
+    /// A known `:shortcode:` with the emoji it stands for.
+    Shortcode {
+        emoji: &'static str,
+        raw: &'a str,
+    },
+    /// A classic emoticon like `:)`, only recognized on its own between whitespace.
+    Emoticon {
+        emoji: &'static str,
+        raw: &'a str,
+    },
+    /// `[[target]]` or `[[target|label]]`, the label defaults to the target.
+    WikiLink {
+        target: &'a str,
+        label: &'a str,
+        raw: &'a str,
+    },
+    /// `<t:epoch>` or `<t:epoch:style>`, the style defaults to `f`. Epochs JS `Date` can't hold
+    /// are left as text.
+    Timestamp {
+        epoch: i64,
+        style: char,
+        raw: &'a str,
+    },
+    /// `<:name:id>`, or `<a:name:id>` for an animated emoji.
+    CustomEmoji {
+        name: &'a str,
+        id: &'a str,
+        animated: bool,
+    },
+    /// A delimiter can open a span when followed by a non-whitespace character and close one when
+    /// preceded by one.
+    Delimiter {
+        kind: DelimiterKind,
+        can_open: bool,
+        can_close: bool,
+    },
+}
+
+pub fn tokenize(text: &str) -> Vec<Inline<'_>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let token = match bytes[pos] {
+            b'=' if bytes[pos..].starts_with(b"==") => {
+                Some(delimiter(text, pos, DelimiterKind::Highlight))
+            }
+            b'|' if bytes[pos..].starts_with(b"||") => {
+                Some(delimiter(text, pos, DelimiterKind::Spoiler))
+            }
+            b'<' => custom_emoji(&text[pos..])
+                .or_else(|| mention(&text[pos..]))
+                .or_else(|| timestamp(&text[pos..]))
+                .or_else(|| emoticon(text, pos)),
+            b':' => shortcode(text, pos).or_else(|| emoticon(text, pos)),
+            b';' => emoticon(text, pos),
+            b'[' => wiki_link(&text[pos..]),
+            b'@' => username(text, pos).map(|name| {
+                let raw = &text[pos..pos + name.len() + 1];
+                (Inline::Username { name, raw }, raw.len())
+            }),
+            _ => None,
+        };
+
+        match token {
+            Some((token, len)) => {
+                if pos > text_start {
+                    tokens.push(Inline::Text(&text[text_start..pos]));
+                }
+                tokens.push(token);
+                pos += len;
+                text_start = pos;
+            }
+            None => pos += 1,
+        }
+    }
+
+    if text_start < text.len() {
+        tokens.push(Inline::Text(&text[text_start..]));
+    }
+
+    tokens
+}
+
+fn delimiter(text: &str, pos: usize, kind: DelimiterKind) -> (Inline<'_>, usize) {
+    let len = kind.as_str().len();
+    let (can_open, can_close) = flanking(text, pos, pos + len);
+
+    (
+        Inline::Delimiter {
+            kind,
+            can_open,
+            can_close,
+        },
+        len,
+    )
+}
+
+fn shortcode(text: &str, pos: usize) -> Option<(Inline<'_>, usize)> {
+    let rest = &text[pos + 1..];
+    let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && !matches!(c, '_' | '+' | '-'))?;
+
+    if len == 0 || !rest[len..].starts_with(':') {
+        return None;
+    }
+
+    let emoji = emoji::shortcode(&rest[..len])?;
+    let raw = &text[pos..pos + len + 2];
+    Some((Inline::Shortcode { emoji, raw }, raw.len()))
+}
+
+fn emoticon(text: &str, pos: usize) -> Option<(Inline<'_>, usize)> {
+    if text[..pos]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_whitespace())
+    {
+        return None;
+    }
+
+    let (emoji, len) = emoji::emoticon(&text[pos..])?;
+    let ends_word = text[pos + len..]
+        .chars()
+        .next()
+        .is_none_or(|c| c.is_whitespace() || matches!(c, '.' | ',' | '!' | '?'));
+
+    ends_word.then(|| {
+        let raw = &text[pos..pos + len];
+        (Inline::Emoticon { emoji, raw }, len)
+    })
+}
+
+fn mention(text: &str) -> Option<(Inline<'_>, usize)> {
+    // `<@!id>` is the legacy form of a mention that used the member nickname.
+    [
+        ("<@&", MentionKind::Role),
+        ("<@!", MentionKind::User),
+        ("<@", MentionKind::User),
+        ("<#", MentionKind::Channel),
+    ]
+    .into_iter()
+    .find_map(|(prefix, kind)| {
+        let (id, len) = bracketed_id(text, prefix)?;
+        let raw = &text[..len];
+        Some((Inline::Mention { kind, id, raw }, len))
+    })
+}
+
+fn wiki_link(text: &str) -> Option<(Inline<'_>, usize)> {
+    let rest = text.strip_prefix("[[")?;
+    let inner = &rest[..rest.find("]]")?];
+
+    if inner.contains(['[', ']', '\n']) {
+        return None;
+    }
+
+    let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+    let target = target.trim();
+    if target.is_empty() {
+        return None;
+    }
+    let label = match label.trim() {
+        "" => target,
+        label => label,
+    };
+    let len = inner.len() + 4;
+
+    Some((
+        Inline::WikiLink {
+            target,
+            label,
+            raw: &text[..len],
+        },
+        len,
+    ))
+}
+
+fn timestamp(text: &str) -> Option<(Inline<'_>, usize)> {
+    let rest = text.strip_prefix("<t:")?;
+    let digits = rest.find(|c: char| !c.is_ascii_digit() && c != '-')?;
+    let epoch = rest[..digits]
+        .parse::<i64>()
+        .ok()
+        .filter(|epoch| epoch.unsigned_abs() <= timestamp::MAX_EPOCH as u64)?;
+
+    let (style, len) = match rest.as_bytes()[digits..] {
+        [b'>', ..] => ('f', 1),
+        [b':', style, b'>', ..] if timestamp::STYLES.contains(&(style as char)) => {
+            (style as char, 3)
+        }
+        _ => return None,
+    };
+
+    let len = text.len() - rest.len() + digits + len;
+    Some((
+        Inline::Timestamp {
+            epoch,
+            style,
+            raw: &text[..len],
+        },
+        len,
+    ))
+}
+
+fn custom_emoji(text: &str) -> Option<(Inline<'_>, usize)> {
+    let (animated, rest) = match text.strip_prefix("<a:") {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix("<:")?),
+    };
+
+    let name_len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')?;
+    if name_len == 0 {
+        return None;
+    }
+
+    let (id, id_len) = bracketed_id(&rest[name_len..], ":")?;
+    let name = &rest[..name_len];
+
+    Some((
+        Inline::CustomEmoji { name, id, animated },
+        text.len() - rest.len() + name_len + id_len,
+    ))
+}
+
+/// Parses a numeric id between `prefix` and a closing `>` at the start of `text`, returning the id
+/// and the length of the whole token.
+fn bracketed_id<'a>(text: &'a str, prefix: &str) -> Option<(&'a str, usize)> {
+    let rest = text.strip_prefix(prefix)?;
+    let len = rest.find(|c: char| !c.is_ascii_digit())?;
+
+    (len > 0 && rest[len..].starts_with('>')).then(|| (&rest[..len], prefix.len() + len + 1))
+}
+
+/// The name of an `@username` mention at `pos`. The `@` must not be part of a word, so email
+/// addresses aren't mistaken for mentions, and a trailing dot ends the sentence, not the name.
+fn username(text: &str, pos: usize) -> Option<&str> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+
+    if text[..pos].chars().next_back().is_some_and(is_name_char) {
+        return None;
+    }
+
+    let rest = &text[pos + 1..];
+    let len = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+    let name = rest[..len].trim_end_matches('.');
+
+    (!name.is_empty()).then_some(name)
+}
+
+/// Whether the delimiter at `start..end` can open (followed by non-whitespace) and close
+/// (preceded by non-whitespace) a span. The edges of a text run count as non-whitespace, they
+/// usually border on other inline markup like `==**bold**==`.
+fn flanking(text: &str, start: usize, end: usize) -> (bool, bool) {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+
+    (
+        after.is_none_or(|c| !c.is_whitespace()),
+        before.is_none_or(|c| !c.is_whitespace()),
+    )
+}
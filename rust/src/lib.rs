@@ -2,17 +2,23 @@
 #![allow(static_mut_refs)]
 
 use anyhow::anyhow;
+use inline::{DelimiterKind, Inline};
 use jsi::de::JsiDeserializeError;
 use jsi::{AsValue, JsiArray, JsiFn, JsiObject, JsiValue, PropName, RuntimeClone};
 use jsi::{FromValue, RuntimeHandle};
 use linkify::LinkFinder;
 use ordered_float::NotNan;
-use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+    TextMergeWithOffset,
+};
 use serde::de::Error;
 use std::borrow::Cow;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::mem::MaybeUninit;
+use std::ops::Range;
 use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
@@ -24,6 +30,11 @@ mod android;
 #[cfg(target_os = "ios")]
 mod ios;
 
+mod emoji;
+mod inline;
+mod math;
+mod timestamp;
+
 static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
 static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
 
@@ -103,6 +114,8 @@ pub struct TextStyle {
     font_families: Option<Vec<Cow<'static, str>>>,
 
     color: Option<[u8; 4]>,
+
+    background_color: Option<[u8; 4]>,
 }
 
 impl Default for TextStyle {
@@ -119,8 +132,16 @@ impl TextStyle {
             decoration: None,
             font_families: None,
             color: None,
+            background_color: None,
         }
     }
+
+    fn base(opts: &MarkdownOptions) -> Self {
+        let mut style = TextStyle::default_with_size(opts.base_font_size);
+        style.font_families = opts.font_families.clone();
+
+        style
+    }
 }
 
 impl Default for FontWeight {
@@ -151,12 +172,283 @@ impl Default for FontStyle {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColumnAlignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl From<Alignment> for ColumnAlignment {
+    fn from(alignment: Alignment) -> Self {
+        match alignment {
+            Alignment::None => ColumnAlignment::None,
+            Alignment::Left => ColumnAlignment::Left,
+            Alignment::Center => ColumnAlignment::Center,
+            Alignment::Right => ColumnAlignment::Right,
+        }
+    }
+}
+
+impl ColumnAlignment {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ColumnAlignment::None => "none",
+            ColumnAlignment::Left => "left",
+            ColumnAlignment::Center => "center",
+            ColumnAlignment::Right => "right",
+        }
+    }
+}
+
+/// The kind of a GitHub style `> [!NOTE]` alert.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl From<BlockQuoteKind> for CalloutKind {
+    fn from(kind: BlockQuoteKind) -> Self {
+        match kind {
+            BlockQuoteKind::Note => CalloutKind::Note,
+            BlockQuoteKind::Tip => CalloutKind::Tip,
+            BlockQuoteKind::Important => CalloutKind::Important,
+            BlockQuoteKind::Warning => CalloutKind::Warning,
+            BlockQuoteKind::Caution => CalloutKind::Caution,
+        }
+    }
+}
+
+impl CalloutKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Important => "important",
+            CalloutKind::Warning => "warning",
+            CalloutKind::Caution => "caution",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Important => "Important",
+            CalloutKind::Warning => "Warning",
+            CalloutKind::Caution => "Caution",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableRow {
+    header: bool,
+    cells: Vec<Vec<TextSegment>>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableBlock {
+    alignments: Vec<ColumnAlignment>,
+    rows: Vec<TableRow>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    #[default]
+    Text,
+    ListMarker,
+    /// `offset` is the byte offset of the `[ ]`/`[x]` marker in the original input.
+    Checkbox {
+        checked: bool,
+        offset: usize,
+    },
+    Table(TableBlock),
+    /// Placeholder for an image, the segment content holds the alt text. The size comes from an
+    /// optional `|width` or `|widthxheight` suffix on the alt text, e.g. `![cat|200x100](url)`.
+    Image {
+        url: String,
+        title: String,
+        width: Option<u32>,
+        height: Option<u32>,
+    },
+    FootnoteReference {
+        label: String,
+        index: usize,
+    },
+    /// Marker in front of a definition in the footnote section appended to the segments.
+    FootnoteDefinition {
+        label: String,
+        index: usize,
+    },
+    Rule {
+        thickness: NotNan<f64>,
+        color: [u8; 4],
+    },
+    /// `tex` is the source of the expression, the content is its Unicode rendering when
+    /// `math_to_unicode` is enabled.
+    Math {
+        tex: String,
+        display: bool,
+    },
+    /// The content is the resolved display name, `id` is kept so a tap can navigate to the user,
+    /// channel or role.
+    Mention {
+        kind: MentionKind,
+        id: String,
+    },
+    /// `epoch` is in seconds, the content holds it preformatted in `style`. Relative (`R`)
+    /// timestamps keep going stale, JS can refresh them from the epoch.
+    Timestamp {
+        epoch: i64,
+        style: char,
+    },
+    /// First line of an alert block, e.g. "Warning", in the color of its kind.
+    CalloutTitle,
+    /// Placeholder for a custom emoji image, the content holds `:name:` as a text fallback.
+    /// `size` follows the font size of the surrounding text.
+    CustomEmoji {
+        id: String,
+        name: String,
+        animated: bool,
+        size: NotNan<f64>,
+    },
+}
+
+impl SegmentKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SegmentKind::Text => "text",
+            SegmentKind::ListMarker => "list_marker",
+            SegmentKind::Checkbox { .. } => "checkbox",
+            SegmentKind::Table(_) => "table",
+            SegmentKind::Image { .. } => "image",
+            SegmentKind::FootnoteReference { .. } => "footnote_reference",
+            SegmentKind::FootnoteDefinition { .. } => "footnote_definition",
+            SegmentKind::Rule { .. } => "rule",
+            SegmentKind::Math { .. } => "math",
+            SegmentKind::Mention { .. } => "mention",
+            SegmentKind::Timestamp { .. } => "timestamp",
+            SegmentKind::CalloutTitle => "callout_title",
+            SegmentKind::CustomEmoji { .. } => "custom_emoji",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct TextSegment {
     content: String,
     style: TextStyle,
 
     href: Option<String>,
+
+    kind: SegmentKind,
+
+    /// List nesting depth of the block this segment belongs to, 0 outside of lists.
+    indent: u8,
+
+    /// Blockquote nesting depth, 0 outside of quotes.
+    quote_depth: u8,
+
+    /// Spoiler group, every segment of one `||spoiler||` shares it so they're revealed together.
+    spoiler: Option<u32>,
+
+    /// Kind of the innermost alert block this segment is part of, title included.
+    callout: Option<CalloutKind>,
+
+    /// Anchor of the heading this segment belongs to, its `{#id}` or a GitHub style slug.
+    anchor: Option<String>,
+
+    /// Index of the heading a `[link](#anchor)` points to, for scrolling to it.
+    jump: Option<usize>,
+}
+
+/// An entry of the heading outline of a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heading {
+    level: u8,
+    text: String,
+    anchor: String,
+    /// Index of the first segment of the heading.
+    index: usize,
+}
+
+/// Which flavor of markdown emphasis to follow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MarkdownDialect {
+    CommonMark,
+    /// Discord semantics, `__text__` underlines instead of making the text bold.
+    Discord,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MentionKind {
+    User,
+    Channel,
+    Role,
+}
+
+impl MentionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MentionKind::User => "user",
+            MentionKind::Channel => "channel",
+            MentionKind::Role => "role",
+        }
+    }
+
+    fn sigil(&self) -> char {
+        match self {
+            MentionKind::User | MentionKind::Role => '@',
+            MentionKind::Channel => '#',
+        }
+    }
+}
+
+/// Display name and color of a mentioned user, channel or role.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MentionTarget {
+    name: String,
+    color: Option<[u8; 4]>,
+}
+
+/// Resolves mention ids to what is rendered for them. Passed to `JsiParseMarkdown` as
+/// `{ users, channels, roles, usernames }`, the first three mapping `[id]: { name, color? }`.
+/// `usernames` maps `[username]: id` for `@username` mentions, unknown names stay plain text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mentions {
+    users: HashMap<String, MentionTarget>,
+    channels: HashMap<String, MentionTarget>,
+    roles: HashMap<String, MentionTarget>,
+    usernames: HashMap<String, String>,
+}
+
+impl Mentions {
+    fn get(&self, kind: MentionKind, id: &str) -> Option<&MentionTarget> {
+        match kind {
+            MentionKind::User => self.users.get(id),
+            MentionKind::Channel => self.channels.get(id),
+            MentionKind::Role => self.roles.get(id),
+        }
+    }
+}
+
+/// What to do with raw HTML in the input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HtmlPolicy {
+    /// Render the HTML as literal text.
+    Escape,
+    /// Drop the tags and keep the text between them.
+    Strip,
+    /// Map a small set of safe tags (`<u>`, `<kbd>`, `<details>`, `<summary>`, `<br>`) to text
+    /// styles and render all other tags as literal text. `<sub>` and `<sup>` are not allowed, a
+    /// text style can't shift the baseline.
+    Allow,
 }
 
 #[derive(Clone, Debug)]
@@ -167,11 +459,44 @@ pub struct MarkdownOptions {
     h4_font_size: f64,
     h5_font_size: f64,
     h6_font_size: f64,
+    subtext_font_size: f64,
+    subtext_color: [u8; 4],
     base_font_size: f64,
     link_color: [u8; 4],
+    /// Href of `[[wiki links]]`, `{slug}` is replaced with the slug of the target page.
+    wiki_link_template: Cow<'static, str>,
+    inline_code_color: Option<[u8; 4]>,
+    inline_code_background_color: [u8; 4],
+    blockquote_color: Option<[u8; 4]>,
+    blockquote_italic: bool,
+    rule_thickness: f64,
+    rule_color: [u8; 4],
+    callout_note_color: [u8; 4],
+    callout_tip_color: [u8; 4],
+    callout_important_color: [u8; 4],
+    callout_warning_color: [u8; 4],
+    callout_caution_color: [u8; 4],
+    html_policy: HtmlPolicy,
+    math_font_family: Option<Cow<'static, str>>,
+    math_color: Option<[u8; 4]>,
+    math_to_unicode: bool,
+    highlight_color: [u8; 4],
+    shortcodes_to_emoji: bool,
+    emoticons_to_emoji: bool,
+    mention_color: [u8; 4],
+    mention_background_color: [u8; 4],
+    mentions: Mentions,
+    channel_placeholder: Cow<'static, str>,
+    role_placeholder: Cow<'static, str>,
+    /// Minutes east of UTC that timestamps are shown in.
+    timezone_offset: i32,
+    locale: Cow<'static, str>,
+    dialect: MarkdownDialect,
     height_multiplier: f64,
     font_families: Option<Vec<Cow<'static, str>>>,
     code_block_font_family: Cow<'static, str>,
+    indented_code_language: Option<Cow<'static, str>>,
+    bullet_glyphs: Vec<Cow<'static, str>>,
     theme: Cow<'static, str>,
 }
 
@@ -184,16 +509,63 @@ impl Default for MarkdownOptions {
             h4_font_size: 23.4,
             h5_font_size: 18.0,
             h6_font_size: 15.3,
+            subtext_font_size: 14.4,
+            subtext_color: [127, 127, 127, 255],
             base_font_size: 18.0,
             link_color: [0, 122, 255, 255],
+            wiki_link_template: Cow::Borrowed("{slug}"),
+            inline_code_color: None,
+            inline_code_background_color: [127, 127, 127, 51],
+            blockquote_color: None,
+            blockquote_italic: false,
+            rule_thickness: 1.0,
+            rule_color: [127, 127, 127, 255],
+            callout_note_color: [9, 105, 218, 255],
+            callout_tip_color: [26, 127, 55, 255],
+            callout_important_color: [130, 80, 223, 255],
+            callout_warning_color: [154, 103, 0, 255],
+            callout_caution_color: [207, 34, 46, 255],
+            html_policy: HtmlPolicy::Escape,
+            math_font_family: None,
+            math_color: None,
+            math_to_unicode: true,
+            highlight_color: [255, 214, 10, 102],
+            shortcodes_to_emoji: true,
+            emoticons_to_emoji: false,
+            mention_color: [88, 101, 242, 255],
+            mention_background_color: [88, 101, 242, 61],
+            mentions: Mentions::default(),
+            channel_placeholder: Cow::Borrowed("#deleted-channel"),
+            role_placeholder: Cow::Borrowed("@deleted-role"),
+            timezone_offset: 0,
+            locale: Cow::Borrowed("en-US"),
+            dialect: MarkdownDialect::CommonMark,
             height_multiplier: 1.0,
             font_families: None,
             code_block_font_family: Cow::Borrowed("monospace"),
+            indented_code_language: None,
+            bullet_glyphs: default_bullet_glyphs(),
             theme: Cow::Borrowed("base16-ocean.dark"),
         }
     }
 }
 
+impl MarkdownOptions {
+    fn callout_color(&self, kind: CalloutKind) -> [u8; 4] {
+        match kind {
+            CalloutKind::Note => self.callout_note_color,
+            CalloutKind::Tip => self.callout_tip_color,
+            CalloutKind::Important => self.callout_important_color,
+            CalloutKind::Warning => self.callout_warning_color,
+            CalloutKind::Caution => self.callout_caution_color,
+        }
+    }
+}
+
+fn default_bullet_glyphs() -> Vec<Cow<'static, str>> {
+    vec![Cow::Borrowed("•"), Cow::Borrowed("◦"), Cow::Borrowed("▪")]
+}
+
 pub fn get_number<'rt>(value: JsiValue<'rt>, rt: &mut RuntimeHandle<'rt>) -> anyhow::Result<f64> {
     if !value.is_number() {
         return Err(anyhow!("Expected a number"));
@@ -202,6 +574,27 @@ pub fn get_number<'rt>(value: JsiValue<'rt>, rt: &mut RuntimeHandle<'rt>) -> any
     Ok(f64::from_value(&value, rt).ok_or(JsiDeserializeError::custom("Expected a number"))?)
 }
 
+pub fn get_rgba<'rt>(value: JsiValue<'rt>, rt: &mut RuntimeHandle<'rt>) -> anyhow::Result<[u8; 4]> {
+    if !value.is_object() {
+        return Err(anyhow!("Expected a color array"));
+    }
+
+    let arr = JsiArray::from_value(&value, rt).ok_or(anyhow!("Expected a color array"))?;
+    let obj = JsiObject::from_value(&value, rt).ok_or(anyhow!("Expected a color array"))?;
+
+    if arr.len(rt) != 4 {
+        return Err(anyhow!("Expected a color array of length 4"));
+    }
+
+    let mut color = [0u8; 4];
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel =
+            get_number(obj.get(get_prop_name(rt, &i.to_string()), rt), rt).unwrap_or(0.0) as u8;
+    }
+
+    Ok(color)
+}
+
 impl<'a> FromValue<'a> for MarkdownOptions {
     fn from_value(value: &JsiValue<'a>, rt: &mut RuntimeHandle<'a>) -> Option<Self> {
         let mut base_font_size = 18.0;
@@ -211,10 +604,40 @@ impl<'a> FromValue<'a> for MarkdownOptions {
         let mut h4_font_size = 23.4;
         let mut h5_font_size = 18.0;
         let mut h6_font_size = 15.3;
+        let mut subtext_font_size = 14.4;
+        let mut subtext_color: [u8; 4] = [127, 127, 127, 255];
         let mut link_color: [u8; 4] = [0, 122, 255, 255];
+        let mut wiki_link_template = Cow::from("{slug}");
+        let mut inline_code_color = None;
+        let mut inline_code_background_color: [u8; 4] = [127, 127, 127, 51];
+        let mut blockquote_color = None;
+        let mut blockquote_italic = false;
+        let mut rule_thickness = 1.0;
+        let mut rule_color: [u8; 4] = [127, 127, 127, 255];
+        let mut callout_note_color: [u8; 4] = [9, 105, 218, 255];
+        let mut callout_tip_color: [u8; 4] = [26, 127, 55, 255];
+        let mut callout_important_color: [u8; 4] = [130, 80, 223, 255];
+        let mut callout_warning_color: [u8; 4] = [154, 103, 0, 255];
+        let mut callout_caution_color: [u8; 4] = [207, 34, 46, 255];
+        let mut html_policy = HtmlPolicy::Escape;
+        let mut math_font_family = None;
+        let mut math_color = None;
+        let mut math_to_unicode = true;
+        let mut highlight_color: [u8; 4] = [255, 214, 10, 102];
+        let mut shortcodes_to_emoji = true;
+        let mut emoticons_to_emoji = false;
+        let mut mention_color: [u8; 4] = [88, 101, 242, 255];
+        let mut mention_background_color: [u8; 4] = [88, 101, 242, 61];
+        let mut channel_placeholder = Cow::from("#deleted-channel");
+        let mut role_placeholder = Cow::from("@deleted-role");
+        let mut timezone_offset = 0;
+        let mut locale = Cow::from("en-US");
+        let mut dialect = MarkdownDialect::CommonMark;
         let mut height_multiplier = 1.0;
         let mut font_families = None;
         let mut code_block_font_family = Cow::from("monospace");
+        let mut indented_code_language = None;
+        let mut bullet_glyphs = default_bullet_glyphs();
         let mut theme = Cow::from("base16-ocean.dark");
 
         if value.is_object() {
@@ -229,6 +652,7 @@ impl<'a> FromValue<'a> for MarkdownOptions {
             h4_font_size = base_font_size * 1.3;
             h5_font_size = base_font_size * 1.0;
             h6_font_size = base_font_size * 0.85;
+            subtext_font_size = base_font_size * 0.8;
 
             if let Ok(val) = get_number(obj.get(get_prop_name(rt, "h1_font_size"), rt), rt) {
                 h1_font_size = val;
@@ -254,21 +678,74 @@ impl<'a> FromValue<'a> for MarkdownOptions {
                 h6_font_size = val;
             }
 
+            if let Ok(val) = get_number(obj.get(get_prop_name(rt, "subtext_font_size"), rt), rt) {
+                subtext_font_size = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "subtext_color"), rt), rt) {
+                subtext_color = val;
+            }
+
             let link_color_prop = obj.get(get_prop_name(rt, "link_color"), rt);
 
             if link_color_prop.is_object() {
-                let arr = JsiArray::from_value(&link_color_prop, rt).unwrap();
-                let obj = JsiObject::from_value(&link_color_prop, rt).unwrap();
+                link_color = get_rgba(link_color_prop, rt).ok()?;
+            }
 
-                if arr.len(rt) != 4 {
-                    return None;
-                }
+            let wiki_link_template_prop = obj.get(get_prop_name(rt, "wiki_link_template"), rt);
+            if wiki_link_template_prop.is_string() {
+                wiki_link_template = String::from_value(&wiki_link_template_prop, rt)?.into();
+            }
 
-                for i in 0..arr.len(rt) {
-                    let val = get_number(obj.get(get_prop_name(rt, &i.to_string()), rt), rt)
-                        .unwrap_or(0.0);
-                    link_color[i] = val as u8;
-                }
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "inline_code_color"), rt), rt) {
+                inline_code_color = Some(val);
+            }
+
+            if let Ok(val) = get_rgba(
+                obj.get(get_prop_name(rt, "inline_code_background_color"), rt),
+                rt,
+            ) {
+                inline_code_background_color = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "blockquote_color"), rt), rt) {
+                blockquote_color = Some(val);
+            }
+
+            let blockquote_italic_prop = obj.get(get_prop_name(rt, "blockquote_italic"), rt);
+            if blockquote_italic_prop.is_bool() {
+                blockquote_italic = bool::from_value(&blockquote_italic_prop, rt)?;
+            }
+
+            if let Ok(val) = get_number(obj.get(get_prop_name(rt, "rule_thickness"), rt), rt) {
+                rule_thickness = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "rule_color"), rt), rt) {
+                rule_color = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "callout_note_color"), rt), rt) {
+                callout_note_color = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "callout_tip_color"), rt), rt) {
+                callout_tip_color = val;
+            }
+
+            if let Ok(val) = get_rgba(
+                obj.get(get_prop_name(rt, "callout_important_color"), rt),
+                rt,
+            ) {
+                callout_important_color = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "callout_warning_color"), rt), rt) {
+                callout_warning_color = val;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "callout_caution_color"), rt), rt) {
+                callout_caution_color = val;
             }
 
             if let Ok(val) = get_number(obj.get(get_prop_name(rt, "height_multiplier"), rt), rt) {
@@ -298,6 +775,106 @@ impl<'a> FromValue<'a> for MarkdownOptions {
                     String::from_value(&code_block_font_family_prop, rt)?.into();
             }
 
+            let indented_code_language_prop =
+                obj.get(get_prop_name(rt, "indented_code_language"), rt);
+            if indented_code_language_prop.is_string() {
+                indented_code_language =
+                    Some(String::from_value(&indented_code_language_prop, rt)?.into());
+            }
+
+            let val = obj.get(get_prop_name(rt, "bullet_glyphs"), rt);
+
+            if val.is_object() {
+                let arr = JsiArray::from_value(&val, rt).unwrap();
+                let obj = JsiObject::from_value(&val, rt).unwrap();
+
+                let mut glyphs = Vec::new();
+                for i in 0..arr.len(rt) {
+                    let glyph =
+                        String::from_value(&obj.get(get_prop_name(rt, &i.to_string()), rt), rt)?;
+                    glyphs.push(Cow::Owned(glyph));
+                }
+
+                if !glyphs.is_empty() {
+                    bullet_glyphs = glyphs;
+                }
+            }
+
+            let html_policy_prop = obj.get(get_prop_name(rt, "html_policy"), rt);
+            if html_policy_prop.is_string() {
+                html_policy = match String::from_value(&html_policy_prop, rt)?.as_str() {
+                    "strip" => HtmlPolicy::Strip,
+                    "allow" => HtmlPolicy::Allow,
+                    _ => HtmlPolicy::Escape,
+                };
+            }
+
+            let math_font_family_prop = obj.get(get_prop_name(rt, "math_font_family"), rt);
+            if math_font_family_prop.is_string() {
+                math_font_family = Some(String::from_value(&math_font_family_prop, rt)?.into());
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "math_color"), rt), rt) {
+                math_color = Some(val);
+            }
+
+            let math_to_unicode_prop = obj.get(get_prop_name(rt, "math_to_unicode"), rt);
+            if math_to_unicode_prop.is_bool() {
+                math_to_unicode = bool::from_value(&math_to_unicode_prop, rt)?;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "highlight_color"), rt), rt) {
+                highlight_color = val;
+            }
+
+            let shortcodes_to_emoji_prop = obj.get(get_prop_name(rt, "shortcodes_to_emoji"), rt);
+            if shortcodes_to_emoji_prop.is_bool() {
+                shortcodes_to_emoji = bool::from_value(&shortcodes_to_emoji_prop, rt)?;
+            }
+
+            let emoticons_to_emoji_prop = obj.get(get_prop_name(rt, "emoticons_to_emoji"), rt);
+            if emoticons_to_emoji_prop.is_bool() {
+                emoticons_to_emoji = bool::from_value(&emoticons_to_emoji_prop, rt)?;
+            }
+
+            if let Ok(val) = get_rgba(obj.get(get_prop_name(rt, "mention_color"), rt), rt) {
+                mention_color = val;
+            }
+
+            if let Ok(val) = get_rgba(
+                obj.get(get_prop_name(rt, "mention_background_color"), rt),
+                rt,
+            ) {
+                mention_background_color = val;
+            }
+
+            let channel_placeholder_prop = obj.get(get_prop_name(rt, "channel_placeholder"), rt);
+            if channel_placeholder_prop.is_string() {
+                channel_placeholder = String::from_value(&channel_placeholder_prop, rt)?.into();
+            }
+
+            let role_placeholder_prop = obj.get(get_prop_name(rt, "role_placeholder"), rt);
+            if role_placeholder_prop.is_string() {
+                role_placeholder = String::from_value(&role_placeholder_prop, rt)?.into();
+            }
+
+            if let Ok(val) = get_number(obj.get(get_prop_name(rt, "timezone_offset"), rt), rt) {
+                timezone_offset = val as i32;
+            }
+
+            let locale_prop = obj.get(get_prop_name(rt, "locale"), rt);
+            if locale_prop.is_string() {
+                locale = String::from_value(&locale_prop, rt)?.into();
+            }
+
+            let dialect_prop = obj.get(get_prop_name(rt, "dialect"), rt);
+            if dialect_prop.is_string() {
+                dialect = match String::from_value(&dialect_prop, rt)?.as_str() {
+                    "discord" => MarkdownDialect::Discord,
+                    _ => MarkdownDialect::CommonMark,
+                };
+            }
+
             let theme_prop = obj.get(get_prop_name(rt, "theme"), rt);
             if theme_prop.is_string() {
                 theme = String::from_value(&theme_prop, rt)?.into();
@@ -312,18 +889,157 @@ impl<'a> FromValue<'a> for MarkdownOptions {
             h4_font_size,
             h5_font_size,
             h6_font_size,
+            subtext_font_size,
+            subtext_color,
             link_color,
+            wiki_link_template,
+            inline_code_color,
+            inline_code_background_color,
+            blockquote_color,
+            blockquote_italic,
+            rule_thickness,
+            rule_color,
+            callout_note_color,
+            callout_tip_color,
+            callout_important_color,
+            callout_warning_color,
+            callout_caution_color,
+            html_policy,
+            math_font_family,
+            math_color,
+            math_to_unicode,
+            highlight_color,
+            shortcodes_to_emoji,
+            emoticons_to_emoji,
+            mention_color,
+            mention_background_color,
+            mentions: Mentions::default(),
+            channel_placeholder,
+            role_placeholder,
+            timezone_offset,
+            locale,
+            dialect,
             height_multiplier,
             font_families,
             code_block_font_family,
+            indented_code_language,
+            bullet_glyphs,
             theme,
         })
     }
 }
 
+impl<'a> FromValue<'a> for Mentions {
+    fn from_value(value: &JsiValue<'a>, rt: &mut RuntimeHandle<'a>) -> Option<Self> {
+        if !value.is_object() {
+            return None;
+        }
+
+        let obj = JsiObject::from_value(value, rt)?;
+
+        Some(Self {
+            users: get_mention_targets(obj.get(get_prop_name(rt, "users"), rt), rt),
+            channels: get_mention_targets(obj.get(get_prop_name(rt, "channels"), rt), rt),
+            roles: get_mention_targets(obj.get(get_prop_name(rt, "roles"), rt), rt),
+            usernames: get_usernames(obj.get(get_prop_name(rt, "usernames"), rt), rt),
+        })
+    }
+}
+
+fn get_mention_targets<'rt>(
+    value: JsiValue<'rt>,
+    rt: &mut RuntimeHandle<'rt>,
+) -> HashMap<String, MentionTarget> {
+    let mut targets = HashMap::new();
+
+    if !value.is_object() {
+        return targets;
+    }
+    let Some(mut obj) = JsiObject::from_value(&value, rt) else {
+        return targets;
+    };
+
+    let ids = obj.properties(rt);
+    for i in 0..ids.len(rt) {
+        let Some(id) = String::from_value(&ids.get(i, rt), rt) else {
+            continue;
+        };
+
+        // Ids are arbitrary, keep them out of the prop name cache.
+        let target = obj.get(PropName::new(&id, rt), rt);
+        if !target.is_object() {
+            continue;
+        }
+        let Some(target) = JsiObject::from_value(&target, rt) else {
+            continue;
+        };
+
+        let name = target.get(get_prop_name(rt, "name"), rt);
+        if !name.is_string() {
+            continue;
+        }
+        let Some(name) = String::from_value(&name, rt) else {
+            continue;
+        };
+        let color = get_rgba(target.get(get_prop_name(rt, "color"), rt), rt).ok();
+
+        targets.insert(id, MentionTarget { name, color });
+    }
+
+    targets
+}
+
+fn get_usernames<'rt>(
+    value: JsiValue<'rt>,
+    rt: &mut RuntimeHandle<'rt>,
+) -> HashMap<String, String> {
+    let mut usernames = HashMap::new();
+
+    if !value.is_object() {
+        return usernames;
+    }
+    let Some(mut obj) = JsiObject::from_value(&value, rt) else {
+        return usernames;
+    };
+
+    let names = obj.properties(rt);
+    for i in 0..names.len(rt) {
+        let Some(name) = String::from_value(&names.get(i, rt), rt) else {
+            continue;
+        };
+
+        let id = obj.get(PropName::new(&name, rt), rt);
+        if !id.is_string() {
+            continue;
+        }
+        if let Some(id) = String::from_value(&id, rt) {
+            usernames.insert(name, id);
+        }
+    }
+
+    usernames
+}
+
 #[cfg(test)]
 mod test;
 
+/// Reads the `(markdown, options?, mentions?)` arguments shared by the host functions.
+fn get_markdown_args(
+    args: &[&JsiValue<'static>],
+    rt: &mut RuntimeHandle<'static>,
+) -> (String, MarkdownOptions) {
+    let markdown_input = std::string::String::from_value(args.get(0).unwrap(), rt).unwrap();
+    let mut markdown_options = match args.get(1) {
+        Some(val) => MarkdownOptions::from_value(val, rt).unwrap_or_default(),
+        None => MarkdownOptions::default(),
+    };
+    if let Some(mentions) = args.get(2).and_then(|val| Mentions::from_value(val, rt)) {
+        markdown_options.mentions = mentions;
+    }
+
+    (markdown_input, markdown_options)
+}
+
 pub fn init(rt: *mut jsi::sys::Runtime) {
     let mut rt = RuntimeHandle::new_unchecked(rt);
 
@@ -331,13 +1047,9 @@ pub fn init(rt: *mut jsi::sys::Runtime) {
 
     let markdown = JsiFn::from_host_fn(
         &PropName::new("JsiParseMarkdown", &mut rt),
-        2,
+        3,
         Box::new(move |_this, args, rt| {
-            let markdown_input = std::string::String::from_value(args.get(0).unwrap(), rt).unwrap();
-            let markdown_options = match args.get(1) {
-                Some(val) => MarkdownOptions::from_value(val, rt).unwrap_or_default(),
-                None => MarkdownOptions::default(),
-            };
+            let (markdown_input, markdown_options) = get_markdown_args(&args, rt);
 
             let segments = parse_markdown(&markdown_input, &markdown_options);
             let arr = JsiArray::new(segments.len(), rt).as_value(rt);
@@ -363,6 +1075,36 @@ pub fn init(rt: *mut jsi::sys::Runtime) {
         &mut rt,
     );
 
+    let outline = JsiFn::from_host_fn(
+        &PropName::new("JsiParseMarkdownOutline", &mut rt),
+        3,
+        Box::new(move |_this, args, rt| {
+            let (markdown_input, markdown_options) = get_markdown_args(&args, rt);
+
+            let headings = parse_outline(&markdown_input, &markdown_options);
+            let arr = JsiArray::new(headings.len(), rt).as_value(rt);
+
+            let mut obj =
+                JsiObject::from_value(&arr, rt).ok_or(anyhow::anyhow!("Failed to create array"))?;
+
+            let _lock = MUTEX.lock().unwrap();
+
+            for (i, heading) in headings.iter().enumerate() {
+                let val = heading_to_jsi_value(rt, heading);
+                obj.set(PropName::new(&i.to_string(), rt), &val, rt);
+            }
+
+            Ok(arr)
+        }),
+        &mut rt,
+    );
+
+    global.set(
+        PropName::new("JsiParseMarkdownOutline", &mut rt),
+        &outline.as_value(&mut rt),
+        &mut rt,
+    );
+
     unsafe {
         STYLE_CACHE.as_mut_ptr().write(HashMap::new());
         STRING_CACHE.as_mut_ptr().write(HashMap::new());
@@ -388,14 +1130,33 @@ pub fn init(rt: *mut jsi::sys::Runtime) {
 }
 
 pub fn parse_markdown(markdown_input: &str, opts: &MarkdownOptions) -> Vec<TextSegment> {
+    parse_markdown_with_outline(markdown_input, opts).0
+}
+
+/// The headings of a document in order, for a table of contents.
+pub fn parse_outline(markdown_input: &str, opts: &MarkdownOptions) -> Vec<Heading> {
+    parse_markdown_with_outline(markdown_input, opts).1
+}
+
+fn parse_markdown_with_outline(
+    markdown_input: &str,
+    opts: &MarkdownOptions,
+) -> (Vec<TextSegment>, Vec<Heading>) {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_MATH);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    options.insert(Options::ENABLE_DEFINITION_LIST);
+    options.insert(Options::ENABLE_GFM);
 
     let linkify = unsafe { &mut *LINKIFY.as_mut_ptr() };
     let links = linkify.links(markdown_input);
 
     let mut new_markdown_input = String::with_capacity(markdown_input.len());
+    let mut inserted_offsets = Vec::new();
     let mut last_pos = 0;
     for link in links {
         if !link.as_str().starts_with("https://") && !link.as_str().starts_with("http://") {
@@ -403,18 +1164,26 @@ pub fn parse_markdown(markdown_input: &str, opts: &MarkdownOptions) -> Vec<TextS
         }
 
         let start = link.start();
-        let end = link.end();
+        if start < last_pos {
+            continue;
+        }
+        let end = extend_over_pipes(markdown_input, link.end());
         new_markdown_input.push_str(&markdown_input[last_pos..start]);
+        inserted_offsets.push(new_markdown_input.len());
         new_markdown_input.push('<');
-        new_markdown_input.push_str(link.as_str());
+        new_markdown_input.push_str(&markdown_input[start..end]);
+        inserted_offsets.push(new_markdown_input.len());
         new_markdown_input.push('>');
         last_pos = end;
     }
 
     new_markdown_input.push_str(&markdown_input[last_pos..]);
-    let new_markdown_input = new_markdown_input.replace('\n', "\\\n");
 
-    let parser = Parser::new_ext(new_markdown_input.as_str(), options);
+    // Collected up front, inline delimiters look ahead for their closer.
+    let events = TextMergeWithOffset::new(
+        Parser::new_ext(new_markdown_input.as_str(), options).into_offset_iter(),
+    )
+    .collect::<Vec<_>>();
 
     let mut segments = Vec::new();
     let mut current_styles: Vec<TextStyle> = Vec::new();
@@ -423,17 +1192,74 @@ pub fn parse_markdown(markdown_input: &str, opts: &MarkdownOptions) -> Vec<TextS
     let mut in_code_block = false;
     let mut code_lang = None;
     let mut code_block_buffer = VecDeque::new();
+    let mut last_content_end: Option<usize> = None;
+    let mut list_stack: Vec<Option<u64>> = Vec::new();
+    let mut quote_depth: usize = 0;
+    let mut table: Option<TableBlock> = None;
+    let mut table_outer_segments = Vec::new();
+    let mut image: Option<(String, String, String)> = None;
+    let mut footnote_labels: Vec<String> = Vec::new();
+    let mut footnote_definitions: Vec<(String, Vec<TextSegment>, Range<usize>)> = Vec::new();
+    let mut footnote_outer_segments = Vec::new();
+    let mut footnote_label: Option<(String, usize)> = None;
+    let mut footnote_outer_state: Option<(Option<usize>, String)> = None;
+    let mut html_open: Vec<(String, usize)> = Vec::new();
+    let mut inline_block_depth = 0;
+    let mut delimiter_pairs = pair_delimiters(&events, 0);
+    let mut highlight_open = false;
+    let mut spoiler: Option<u32> = None;
+    let mut next_spoiler: u32 = 0;
+    let mut subtext: Option<Subtext> = None;
+    let mut heading: Option<(usize, u8, Option<String>)> = None;
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut callouts: Vec<(CalloutKind, usize)> = Vec::new();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64);
+
+    for (position, (event, range)) in events.iter().cloned().enumerate() {
+        let starts_content = match &event {
+            // Definitions are moved to the end, they don't separate the content around them.
+            Event::Start(Tag::FootnoteDefinition(_)) => false,
+            Event::Start(_)
+            | Event::Text(_)
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_)
+            | Event::InlineMath(_)
+            | Event::DisplayMath(_)
+            | Event::Rule => true,
+            _ => false,
+        };
+
+        if !in_code_block && starts_content {
+            carry_line_breaks(
+                &new_markdown_input,
+                &mut last_content_end,
+                range.start,
+                &mut pending_breaks,
+            );
+        }
 
-    for event in parser {
         match event {
             Event::Start(tag) => {
-                let mut new_style = current_styles.last().cloned().unwrap_or_else(|| {
-                    let mut style = TextStyle::default_with_size(opts.base_font_size);
-                    style.font_families = opts.font_families.clone();
+                let mut new_style = current_styles
+                    .last()
+                    .cloned()
+                    .unwrap_or_else(|| TextStyle::base(opts));
+
+                if is_inline_container(&tag) {
+                    inline_block_depth = current_styles.len();
+                    delimiter_pairs = pair_delimiters(&events, position + 1);
+                }
 
-                    style
-                });
                 match tag {
+                    Tag::Strong
+                        if opts.dialect == MarkdownDialect::Discord
+                            && new_markdown_input[range.start..].starts_with("__") =>
+                    {
+                        new_style.decoration = Some(TextDecoration::Underline);
+                    }
                     Tag::Strong => {
                         if new_style.font_style.is_none() {
                             new_style.font_style = Some(FontStyle::default());
@@ -449,11 +1275,18 @@ pub fn parse_markdown(markdown_input: &str, opts: &MarkdownOptions) -> Vec<TextS
                     Tag::Strikethrough => {
                         new_style.decoration = Some(TextDecoration::LineThrough);
                     }
-                    Tag::CodeBlock(pulldown_cmark::CodeBlockKind::Fenced(lang)) => {
+                    Tag::CodeBlock(kind) => {
                         in_code_block = true;
-                        code_lang = Some(lang.to_string());
+                        code_lang = match kind {
+                            CodeBlockKind::Fenced(lang) => Some(lang.to_string()),
+                            CodeBlockKind::Indented => {
+                                opts.indented_code_language.as_deref().map(str::to_string)
+                            }
+                        };
                     }
-                    Tag::Heading { level, .. } => {
+                    Tag::Heading { level, id, .. } => {
+                        heading = Some((segments.len(), level as u8, id.map(|id| id.to_string())));
+
                         if new_style.font_style.is_none() {
                             new_style.font_style = Some(FontStyle::default());
                         }
@@ -472,55 +1305,1183 @@ pub fn parse_markdown(markdown_input: &str, opts: &MarkdownOptions) -> Vec<TextS
                         new_style.decoration = Some(TextDecoration::Underline);
                         new_style.color = Some(opts.link_color.clone());
                     }
-                    _ => {}
-                }
-                current_styles.push(new_style);
-            }
-            Event::End(tag) => {
-                match tag {
-                    TagEnd::CodeBlock => {
-                        in_code_block = false;
-                        let code = code_block_buffer.drain(..).collect::<String>();
-                        let highlighted_segments =
-                            highlight_code_block(&code, code_lang.as_deref(), opts);
-                        segments.extend(highlighted_segments);
-                        code_lang = None;
+                    Tag::BlockQuote(kind) => {
+                        quote_depth += 1;
+                        if let Some(color) = opts.blockquote_color {
+                            new_style.color = Some(color);
+                        }
+                        if opts.blockquote_italic {
+                            if new_style.font_style.is_none() {
+                                new_style.font_style = Some(FontStyle::default());
+                            }
+                            new_style.font_style.as_mut().unwrap().slant = FontSlant::Italic;
+                        }
+
+                        // The `[!KIND]` marker line is replaced by a title.
+                        if let Some(kind) = kind.map(CalloutKind::from) {
+                            callouts.push((kind, segments.len()));
+
+                            let mut style = new_style.clone();
+                            if style.font_style.is_none() {
+                                style.font_style = Some(FontStyle::default());
+                            }
+                            style.font_style.as_mut().unwrap().weight = FontWeight::Bold;
+                            style.color = Some(opts.callout_color(kind));
+
+                            segments.push(TextSegment {
+                                content: format!("{}{}", pending_breaks, kind.title()),
+                                style,
+                                kind: SegmentKind::CalloutTitle,
+                                indent: clamp_depth(list_stack.len()),
+                                quote_depth: clamp_depth(quote_depth),
+                                ..Default::default()
+                            });
+                            pending_breaks.clear();
+                        }
                     }
-                    TagEnd::Link { .. } => link_href = None,
-                    TagEnd::Paragraph => {
-                        pending_breaks.push_str("\n\n");
+                    Tag::List(start) => {
+                        list_stack.push(start);
                     }
-                    _ => {}
-                }
-                current_styles.pop();
+                    Tag::DefinitionListTitle => {
+                        if new_style.font_style.is_none() {
+                            new_style.font_style = Some(FontStyle::default());
+                        }
+                        new_style.font_style.as_mut().unwrap().weight = FontWeight::Bold;
+                    }
+                    // Definitions are indented like an unmarked list level below their term.
+                    Tag::DefinitionListDefinition => {
+                        list_stack.push(None);
+                    }
+                    Tag::Image {
+                        dest_url, title, ..
+                    } => {
+                        if !pending_breaks.is_empty() {
+                            segments.push(TextSegment {
+                                content: std::mem::take(&mut pending_breaks),
+                                style: new_style.clone(),
+                                indent: clamp_depth(list_stack.len()),
+                                quote_depth: clamp_depth(quote_depth),
+                                ..Default::default()
+                            });
+                        }
+                        image = Some((dest_url.to_string(), title.to_string(), String::new()));
+                    }
+                    Tag::FootnoteDefinition(ref label) => {
+                        footnote_label = Some((label.to_string(), headings.len()));
+                        footnote_outer_segments = std::mem::take(&mut segments);
+                        footnote_outer_state =
+                            Some((last_content_end.take(), std::mem::take(&mut pending_breaks)));
+                    }
+                    Tag::Table(alignments) => {
+                        table = Some(TableBlock {
+                            alignments: alignments.into_iter().map(Into::into).collect(),
+                            rows: Vec::new(),
+                        });
+                        table_outer_segments = std::mem::take(&mut segments);
+                    }
+                    Tag::TableHead | Tag::TableRow => {
+                        let header = matches!(tag, Tag::TableHead);
+                        if header {
+                            if new_style.font_style.is_none() {
+                                new_style.font_style = Some(FontStyle::default());
+                            }
+                            new_style.font_style.as_mut().unwrap().weight = FontWeight::Bold;
+                        }
+
+                        if let Some(table) = table.as_mut() {
+                            table.rows.push(TableRow {
+                                header,
+                                cells: Vec::new(),
+                            });
+                        }
+                    }
+                    Tag::TableCell => {
+                        // Cells are laid out by the renderer, the breaks between them are not
+                        // part of their content.
+                        pending_breaks.clear();
+                    }
+                    Tag::Item => {
+                        let depth = list_stack.len();
+                        let marker = match list_stack.last_mut() {
+                            Some(Some(number)) => {
+                                let marker = format!("{}. ", number);
+                                *number += 1;
+                                marker
+                            }
+                            _ => {
+                                let glyphs = &opts.bullet_glyphs;
+                                format!("{} ", glyphs[depth.saturating_sub(1) % glyphs.len()])
+                            }
+                        };
+
+                        segments.push(TextSegment {
+                            content: format!("{}{}", pending_breaks, marker),
+                            style: new_style.clone(),
+                            kind: SegmentKind::ListMarker,
+                            indent: clamp_depth(depth),
+                            quote_depth: clamp_depth(quote_depth),
+                            ..Default::default()
+                        });
+                        pending_breaks.clear();
+                    }
+                    _ => {}
+                }
+                current_styles.push(new_style);
+            }
+            Event::End(tag) => {
+                if ends_inline_container(&tag) {
+                    subtext = None;
+                    delimiter_pairs = pair_delimiters(&events, position + 1);
+                }
+
+                // HTML tags left open inside a block are closed together with it.
+                while html_open
+                    .last()
+                    .is_some_and(|(_, depth)| *depth == current_styles.len())
+                {
+                    html_open.pop();
+                    current_styles.pop();
+                }
+
+                match tag {
+                    TagEnd::Heading(_) => {
+                        if let Some((start, level, id)) = heading.take() {
+                            let text = segments[start..]
+                                .iter()
+                                .map(|segment| segment.content.as_str())
+                                .collect::<String>();
+                            let text = text.trim().to_string();
+                            let anchor = id.unwrap_or_else(|| unique_slug(&headings, &text));
+
+                            for segment in &mut segments[start..] {
+                                segment.anchor = Some(anchor.clone());
+                            }
+                            headings.push(Heading {
+                                level,
+                                text,
+                                anchor,
+                                index: start,
+                            });
+                        }
+
+                        // A setext underline isn't content, only the line break after it counts.
+                        last_content_end = Some(block_content_end(&new_markdown_input, range.end));
+                    }
+                    TagEnd::CodeBlock => {
+                        in_code_block = false;
+                        let code = code_block_buffer.drain(..).collect::<String>();
+                        let code = code.strip_suffix('\n').unwrap_or(&code);
+                        let mut highlighted_segments =
+                            highlight_code_block(code, code_lang.as_deref(), opts);
+                        if let Some(first) = highlighted_segments.first_mut() {
+                            first.content.insert_str(0, &pending_breaks);
+                            pending_breaks.clear();
+                        }
+                        for segment in highlighted_segments.iter_mut() {
+                            segment.indent = clamp_depth(list_stack.len());
+                            segment.quote_depth = clamp_depth(quote_depth);
+                        }
+                        segments.extend(highlighted_segments);
+                        code_lang = None;
+
+                        last_content_end = Some(block_content_end(&new_markdown_input, range.end));
+                    }
+                    TagEnd::Link { .. } => link_href = None,
+                    TagEnd::BlockQuote(kind) => {
+                        quote_depth -= 1;
+
+                        if let Some((callout, start)) = kind.and_then(|_| callouts.pop()) {
+                            for segment in segments.iter_mut().skip(start) {
+                                segment.callout.get_or_insert(callout);
+                            }
+                        }
+                    }
+                    TagEnd::List(_) | TagEnd::DefinitionListDefinition => {
+                        list_stack.pop();
+                    }
+                    TagEnd::Image => {
+                        if let Some((url, title, alt)) = image.take() {
+                            let (alt, width, height) = split_image_size(&alt);
+                            segments.push(TextSegment {
+                                content: alt.to_string(),
+                                style: subtext_style(
+                                    current_styles.last().cloned().unwrap_or_default(),
+                                    subtext,
+                                    opts,
+                                ),
+                                href: link_href.clone(),
+                                kind: SegmentKind::Image {
+                                    url,
+                                    title,
+                                    width,
+                                    height,
+                                },
+                                indent: clamp_depth(list_stack.len()),
+                                quote_depth: clamp_depth(quote_depth),
+                                spoiler,
+                                ..Default::default()
+                            });
+                            last_content_end = Some(range.end);
+                        }
+                    }
+                    TagEnd::FootnoteDefinition => {
+                        let definition = std::mem::replace(
+                            &mut segments,
+                            std::mem::take(&mut footnote_outer_segments),
+                        );
+                        if let Some((label, first_heading)) = footnote_label.take() {
+                            footnote_definitions.push((
+                                label,
+                                definition,
+                                first_heading..headings.len(),
+                            ));
+                        }
+                        if let Some((outer_end, outer_breaks)) = footnote_outer_state.take() {
+                            last_content_end = outer_end.and(last_content_end);
+                            pending_breaks = outer_breaks;
+                        }
+                    }
+                    TagEnd::TableCell => {
+                        let cell = std::mem::take(&mut segments);
+                        if let Some(row) = table.as_mut().and_then(|table| table.rows.last_mut()) {
+                            row.cells.push(cell);
+                        }
+                    }
+                    TagEnd::Table => {
+                        segments = std::mem::take(&mut table_outer_segments);
+                        if let Some(table) = table.take() {
+                            pending_breaks.clear();
+                            segments.push(TextSegment {
+                                style: TextStyle::base(opts),
+                                kind: SegmentKind::Table(table),
+                                indent: clamp_depth(list_stack.len()),
+                                quote_depth: clamp_depth(quote_depth),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                current_styles.pop();
             }
             Event::Text(text) => {
                 if in_code_block {
                     code_block_buffer.push_back(text.to_string());
+                } else if let Some((_, _, alt)) = image.as_mut() {
+                    alt.push_str(&text);
                 } else {
-                    let style = current_styles.last().cloned().unwrap_or_default();
-                    let content = format!("{}{}", pending_breaks, text);
-                    pending_breaks.clear();
+                    let mut text = &*text;
+                    if subtext.is_none() && at_line_start(&new_markdown_input, range.start) {
+                        if let Some(rest) = text.strip_prefix("-# ") {
+                            subtext = Some(Subtext {
+                                color: current_styles
+                                    .get(inline_block_depth)
+                                    .and_then(|style| style.color),
+                            });
+                            text = rest;
+                        }
+                    }
 
+                    let push_run =
+                        |segments: &mut Vec<TextSegment>, content, highlighted, spoiler| {
+                            let mut style = subtext_style(
+                                current_styles.last().cloned().unwrap_or_default(),
+                                subtext,
+                                opts,
+                            );
+                            if highlighted {
+                                style.background_color = Some(opts.highlight_color);
+                            }
+
+                            segments.push(TextSegment {
+                                content,
+                                style,
+                                href: link_href.clone(),
+                                indent: clamp_depth(list_stack.len()),
+                                quote_depth: clamp_depth(quote_depth),
+                                spoiler,
+                                ..Default::default()
+                            });
+                        };
+
+                    let tokens = inline::tokenize(text)
+                        .into_iter()
+                        .map(|token| match token {
+                            // `@username` is only a mention when the name is known.
+                            Inline::Username { name, raw } => {
+                                match opts.mentions.usernames.get(name) {
+                                    Some(id) => Inline::Mention {
+                                        kind: MentionKind::User,
+                                        id,
+                                        raw,
+                                    },
+                                    None => Inline::Text(raw),
+                                }
+                            }
+                            token => token,
+                        })
+                        .collect::<Vec<_>>();
+                    let mut run = std::mem::take(&mut pending_breaks);
+                    let mut delimiter = 0;
+
+                    for token in &tokens {
+                        match *token {
+                            Inline::Text(text) => run.push_str(text),
+                            Inline::Shortcode { emoji, raw } => {
+                                run.push_str(if opts.shortcodes_to_emoji { emoji } else { raw })
+                            }
+                            Inline::Emoticon { emoji, raw } => {
+                                run.push_str(if opts.emoticons_to_emoji { emoji } else { raw })
+                            }
+                            // Link text stays as it was written.
+                            Inline::Mention { raw, .. } if link_href.is_some() => run.push_str(raw),
+                            // Known names were turned into mentions above.
+                            Inline::Username { raw, .. } => run.push_str(raw),
+                            Inline::WikiLink { raw, .. } if link_href.is_some() => {
+                                run.push_str(raw)
+                            }
+                            Inline::Delimiter { kind, .. } if link_href.is_some() => {
+                                run.push_str(kind.as_str())
+                            }
+                            Inline::WikiLink { target, label, .. } => {
+                                if !run.is_empty() {
+                                    push_run(
+                                        &mut segments,
+                                        std::mem::take(&mut run),
+                                        highlight_open,
+                                        spoiler,
+                                    );
+                                }
+
+                                let mut style = subtext_style(
+                                    current_styles
+                                        .last()
+                                        .cloned()
+                                        .unwrap_or_else(|| TextStyle::base(opts)),
+                                    subtext,
+                                    opts,
+                                );
+                                style.decoration = Some(TextDecoration::Underline);
+                                style.color = Some(opts.link_color);
+
+                                segments.push(TextSegment {
+                                    content: label.to_string(),
+                                    style,
+                                    href: Some(
+                                        opts.wiki_link_template.replace("{slug}", &slugify(target)),
+                                    ),
+                                    indent: clamp_depth(list_stack.len()),
+                                    quote_depth: clamp_depth(quote_depth),
+                                    spoiler,
+                                    ..Default::default()
+                                });
+                            }
+                            Inline::Timestamp {
+                                epoch,
+                                style: format,
+                                raw,
+                            } => {
+                                let Some(content) = timestamp::format(
+                                    epoch,
+                                    format,
+                                    opts.timezone_offset,
+                                    &opts.locale,
+                                    now,
+                                ) else {
+                                    run.push_str(raw);
+                                    continue;
+                                };
+
+                                if !run.is_empty() {
+                                    push_run(
+                                        &mut segments,
+                                        std::mem::take(&mut run),
+                                        highlight_open,
+                                        spoiler,
+                                    );
+                                }
+
+                                segments.push(TextSegment {
+                                    content,
+                                    style: subtext_style(
+                                        current_styles
+                                            .last()
+                                            .cloned()
+                                            .unwrap_or_else(|| TextStyle::base(opts)),
+                                        subtext,
+                                        opts,
+                                    ),
+                                    href: link_href.clone(),
+                                    kind: SegmentKind::Timestamp {
+                                        epoch,
+                                        style: format,
+                                    },
+                                    indent: clamp_depth(list_stack.len()),
+                                    quote_depth: clamp_depth(quote_depth),
+                                    spoiler,
+                                    ..Default::default()
+                                });
+                            }
+                            Inline::CustomEmoji { name, id, animated } => {
+                                if !run.is_empty() {
+                                    push_run(
+                                        &mut segments,
+                                        std::mem::take(&mut run),
+                                        highlight_open,
+                                        spoiler,
+                                    );
+                                }
+
+                                let style = subtext_style(
+                                    current_styles
+                                        .last()
+                                        .cloned()
+                                        .unwrap_or_else(|| TextStyle::base(opts)),
+                                    subtext,
+                                    opts,
+                                );
+                                let size = style
+                                    .font_size
+                                    .unwrap_or_else(|| NotNan::new(opts.base_font_size).unwrap());
+
+                                segments.push(TextSegment {
+                                    content: format!(":{}:", name),
+                                    style,
+                                    href: link_href.clone(),
+                                    kind: SegmentKind::CustomEmoji {
+                                        id: id.to_string(),
+                                        name: name.to_string(),
+                                        animated,
+                                        size,
+                                    },
+                                    indent: clamp_depth(list_stack.len()),
+                                    quote_depth: clamp_depth(quote_depth),
+                                    spoiler,
+                                    ..Default::default()
+                                });
+                            }
+                            Inline::Mention { kind, id, raw } => {
+                                if !run.is_empty() {
+                                    push_run(
+                                        &mut segments,
+                                        std::mem::take(&mut run),
+                                        highlight_open,
+                                        spoiler,
+                                    );
+                                }
+
+                                let target = opts.mentions.get(kind, id);
+                                let mut style = subtext_style(
+                                    current_styles
+                                        .last()
+                                        .cloned()
+                                        .unwrap_or_else(|| TextStyle::base(opts)),
+                                    subtext,
+                                    opts,
+                                );
+                                style.color = Some(
+                                    target
+                                        .and_then(|target| target.color)
+                                        .unwrap_or(opts.mention_color),
+                                );
+                                style.background_color = Some(opts.mention_background_color);
+
+                                let content = match (target, kind) {
+                                    (Some(target), _) => format!("{}{}", kind.sigil(), target.name),
+                                    // A known `@username` without a display name stays as typed.
+                                    (None, MentionKind::User) if raw.starts_with('@') => {
+                                        raw.to_string()
+                                    }
+                                    (None, MentionKind::User) => format!("@{}", id),
+                                    (None, MentionKind::Channel) => {
+                                        opts.channel_placeholder.to_string()
+                                    }
+                                    (None, MentionKind::Role) => opts.role_placeholder.to_string(),
+                                };
+
+                                segments.push(TextSegment {
+                                    content,
+                                    style,
+                                    kind: SegmentKind::Mention {
+                                        kind,
+                                        id: id.to_string(),
+                                    },
+                                    indent: clamp_depth(list_stack.len()),
+                                    quote_depth: clamp_depth(quote_depth),
+                                    spoiler,
+                                    ..Default::default()
+                                });
+                            }
+                            Inline::Delimiter { kind, .. } => {
+                                let open = match kind {
+                                    DelimiterKind::Highlight => highlight_open,
+                                    DelimiterKind::Spoiler => spoiler.is_some(),
+                                };
+
+                                delimiter += 1;
+                                if !delimiter_pairs.contains(&(position, delimiter - 1)) {
+                                    run.push_str(kind.as_str());
+                                    continue;
+                                }
+
+                                if !run.is_empty() {
+                                    push_run(
+                                        &mut segments,
+                                        std::mem::take(&mut run),
+                                        highlight_open,
+                                        spoiler,
+                                    );
+                                }
+
+                                match kind {
+                                    DelimiterKind::Highlight => highlight_open = !open,
+                                    DelimiterKind::Spoiler if open => spoiler = None,
+                                    DelimiterKind::Spoiler => {
+                                        spoiler = Some(next_spoiler);
+                                        next_spoiler += 1;
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    if !run.is_empty() {
+                        push_run(&mut segments, run, highlight_open, spoiler);
+                    }
+                    last_content_end = Some(range.end);
+                }
+            }
+            Event::Code(code) if image.is_some() => {
+                if let Some((_, _, alt)) = image.as_mut() {
+                    alt.push_str(&code);
+                }
+            }
+            Event::Code(code) => {
+                let mut style = subtext_style(
+                    current_styles
+                        .last()
+                        .cloned()
+                        .unwrap_or_else(|| TextStyle::base(opts)),
+                    subtext,
+                    opts,
+                );
+                style.font_families = Some(vec![opts.code_block_font_family.clone()]);
+                style.background_color = Some(opts.inline_code_background_color);
+                if let Some(color) = opts.inline_code_color {
+                    style.color = Some(color);
+                }
+
+                if !pending_breaks.is_empty() {
                     segments.push(TextSegment {
-                        content,
-                        style,
-                        href: link_href.clone(),
+                        content: std::mem::take(&mut pending_breaks),
+                        style: subtext_style(
+                            current_styles.last().cloned().unwrap_or_default(),
+                            subtext,
+                            opts,
+                        ),
+                        indent: clamp_depth(list_stack.len()),
+                        quote_depth: clamp_depth(quote_depth),
+                        ..Default::default()
                     });
                 }
+
+                segments.push(TextSegment {
+                    content: code.to_string(),
+                    style,
+                    href: link_href.clone(),
+                    indent: clamp_depth(list_stack.len()),
+                    quote_depth: clamp_depth(quote_depth),
+                    spoiler,
+                    ..Default::default()
+                });
+                last_content_end = Some(range.end);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                let html = html.strip_suffix('\n').unwrap_or(&html);
+                let mut visible = false;
+
+                for token in html_tokens(html) {
+                    let literal = match token {
+                        HtmlToken::Text(text) => Some(text),
+                        HtmlToken::Tag { raw, .. } if opts.html_policy == HtmlPolicy::Escape => {
+                            Some(raw)
+                        }
+                        HtmlToken::Tag { name, .. }
+                            if opts.html_policy == HtmlPolicy::Allow && name == "br" =>
+                        {
+                            Some("\n")
+                        }
+                        HtmlToken::Tag { name, closing, raw }
+                            if opts.html_policy == HtmlPolicy::Allow =>
+                        {
+                            let style = current_styles
+                                .last()
+                                .cloned()
+                                .unwrap_or_else(|| TextStyle::base(opts));
+
+                            match html_tag_style(style, name, opts) {
+                                Some(_) if closing => {
+                                    if html_open.last().is_some_and(|(open, depth)| {
+                                        open == name && *depth == current_styles.len()
+                                    }) {
+                                        html_open.pop();
+                                        current_styles.pop();
+                                    }
+                                    None
+                                }
+                                Some(style) => {
+                                    current_styles.push(style);
+                                    html_open.push((name.to_string(), current_styles.len()));
+                                    None
+                                }
+                                None => Some(raw),
+                            }
+                        }
+                        HtmlToken::Tag { .. } => None,
+                    };
+
+                    let Some(literal) = literal else {
+                        continue;
+                    };
+
+                    for (i, line) in literal.split('\n').enumerate() {
+                        if i > 0 {
+                            pending_breaks.push('\n');
+                        }
+                        if line.is_empty() {
+                            continue;
+                        }
+
+                        segments.push(TextSegment {
+                            content: format!("{}{}", pending_breaks, line),
+                            style: subtext_style(
+                                current_styles.last().cloned().unwrap_or_default(),
+                                subtext,
+                                opts,
+                            ),
+                            href: link_href.clone(),
+                            indent: clamp_depth(list_stack.len()),
+                            quote_depth: clamp_depth(quote_depth),
+                            spoiler,
+                            ..Default::default()
+                        });
+                        pending_breaks.clear();
+                        visible = true;
+                    }
+                }
+
+                // Lines that only held stripped tags shouldn't add line breaks of their own.
+                last_content_end = if visible {
+                    Some(block_content_end(&new_markdown_input, range.end))
+                } else {
+                    None
+                };
+            }
+            Event::InlineMath(ref tex) | Event::DisplayMath(ref tex) => {
+                let display = matches!(event, Event::DisplayMath(_));
+                let mut style = subtext_style(
+                    current_styles
+                        .last()
+                        .cloned()
+                        .unwrap_or_else(|| TextStyle::base(opts)),
+                    subtext,
+                    opts,
+                );
+                if let Some(family) = &opts.math_font_family {
+                    style.font_families = Some(vec![family.clone()]);
+                }
+                if let Some(color) = opts.math_color {
+                    style.color = Some(color);
+                }
+
+                let math = if opts.math_to_unicode {
+                    math::tex_to_unicode(tex)
+                } else {
+                    tex.to_string()
+                };
+
+                segments.push(TextSegment {
+                    content: format!("{}{}", pending_breaks, math),
+                    style,
+                    href: link_href.clone(),
+                    kind: SegmentKind::Math {
+                        tex: tex.to_string(),
+                        display,
+                    },
+                    indent: clamp_depth(list_stack.len()),
+                    quote_depth: clamp_depth(quote_depth),
+                    spoiler,
+                    ..Default::default()
+                });
+                pending_breaks.clear();
+                last_content_end = Some(range.end);
+            }
+            Event::Rule => {
+                pending_breaks.clear();
+                segments.push(TextSegment {
+                    style: TextStyle::base(opts),
+                    kind: SegmentKind::Rule {
+                        thickness: NotNan::new(opts.rule_thickness).unwrap_or_default(),
+                        color: opts.rule_color,
+                    },
+                    indent: clamp_depth(list_stack.len()),
+                    quote_depth: clamp_depth(quote_depth),
+                    ..Default::default()
+                });
+                last_content_end = Some(block_content_end(&new_markdown_input, range.end));
+            }
+            Event::FootnoteReference(label) => {
+                let index = footnote_index(&mut footnote_labels, &label);
+                let mut style = subtext_style(
+                    current_styles
+                        .last()
+                        .cloned()
+                        .unwrap_or_else(|| TextStyle::base(opts)),
+                    subtext,
+                    opts,
+                );
+                style.color = Some(opts.link_color);
+
+                segments.push(TextSegment {
+                    content: format!("{}{}", pending_breaks, superscript(index)),
+                    style,
+                    kind: SegmentKind::FootnoteReference {
+                        label: label.to_string(),
+                        index,
+                    },
+                    indent: clamp_depth(list_stack.len()),
+                    quote_depth: clamp_depth(quote_depth),
+                    spoiler,
+                    ..Default::default()
+                });
+                pending_breaks.clear();
+                last_content_end = Some(range.end);
+            }
+            Event::TaskListMarker(checked) => {
+                let mut content = String::new();
+                let mut style = current_styles.last().cloned().unwrap_or_default();
+
+                // A checkbox replaces the bullet of its item, ordered items keep their number.
+                if matches!(list_stack.last(), Some(None)) {
+                    if let Some(marker) =
+                        segments.pop_if(|segment| segment.kind == SegmentKind::ListMarker)
+                    {
+                        content = marker
+                            .content
+                            .trim_end_matches(|c: char| c != '\n')
+                            .to_string();
+                        style = marker.style;
+                    }
+                }
+
+                content.push_str(&pending_breaks);
+                pending_breaks.clear();
+                content.push_str(if checked { "☑ " } else { "☐ " });
+
+                segments.push(TextSegment {
+                    content,
+                    style,
+                    kind: SegmentKind::Checkbox {
+                        checked,
+                        offset: source_offset(&inserted_offsets, range.start),
+                    },
+                    indent: clamp_depth(list_stack.len()),
+                    quote_depth: clamp_depth(quote_depth),
+                    ..Default::default()
+                });
+                last_content_end = Some(range.end);
             }
             Event::SoftBreak | Event::HardBreak => {
                 if in_code_block {
                     code_block_buffer.push_back("\n".to_string());
                 } else {
+                    subtext = None;
                     pending_breaks.push('\n');
+                    last_content_end = Some(range.end);
+                }
+            }
+        }
+    }
+
+    // Definitions that are never referenced are numbered after the referenced ones.
+    for (label, _, _) in &footnote_definitions {
+        footnote_index(&mut footnote_labels, label);
+    }
+    footnote_definitions.sort_by_key(|(label, _, _)| footnote_index(&mut footnote_labels, label));
+
+    for (i, (label, definition, definition_headings)) in
+        footnote_definitions.into_iter().enumerate()
+    {
+        let index = footnote_index(&mut footnote_labels, &label);
+        let separator = if i == 0 { "\n\n" } else { "\n" };
+
+        segments.push(TextSegment {
+            content: format!("{}{} ", separator, superscript(index)),
+            style: TextStyle::base(opts),
+            kind: SegmentKind::FootnoteDefinition { label, index },
+            ..Default::default()
+        });
+
+        // Headings in a definition were indexed from its start.
+        for heading in &mut headings[definition_headings] {
+            heading.index += segments.len();
+        }
+        segments.extend(definition);
+    }
+    // Definitions are moved to the end, so are their headings.
+    headings.sort_by_key(|heading| heading.index);
+
+    resolve_jumps(&mut segments, &headings);
+
+    (segments, headings)
+}
+
+/// GitHub style slug: lowercase, punctuation dropped and spaces turned into dashes.
+fn slugify(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Slug for a heading, repeated slugs get a `-1`, `-2`, ... suffix.
+fn unique_slug(headings: &[Heading], text: &str) -> String {
+    let slug = slugify(text);
+
+    let mut unique = slug.clone();
+    let mut suffix = 1;
+    while headings.iter().any(|heading| heading.anchor == unique) {
+        unique = format!("{}-{}", slug, suffix);
+        suffix += 1;
+    }
+
+    unique
+}
+
+/// Marks links to `#anchor` of a heading in the same document as jumps to that heading.
+fn resolve_jumps(segments: &mut [TextSegment], headings: &[Heading]) {
+    for segment in segments {
+        if let SegmentKind::Table(table) = &mut segment.kind {
+            for cell in table.rows.iter_mut().flat_map(|row| row.cells.iter_mut()) {
+                resolve_jumps(cell, headings);
+            }
+        }
+
+        segment.jump = segment
+            .href
+            .as_deref()
+            .and_then(|href| href.strip_prefix('#'))
+            .and_then(|anchor| headings.iter().position(|heading| heading.anchor == anchor));
+    }
+}
+
+/// Returns the 1-based number of a footnote, numbering labels in the order they are first seen.
+fn footnote_index(footnote_labels: &mut Vec<String>, label: &str) -> usize {
+    match footnote_labels.iter().position(|known| known == label) {
+        Some(position) => position + 1,
+        None => {
+            footnote_labels.push(label.to_string());
+            footnote_labels.len()
+        }
+    }
+}
+
+fn superscript(number: usize) -> String {
+    number
+        .to_string()
+        .chars()
+        .map(|digit| match digit {
+            '0' => '⁰',
+            '1' => '¹',
+            '2' => '²',
+            '3' => '³',
+            '4' => '⁴',
+            '5' => '⁵',
+            '6' => '⁶',
+            '7' => '⁷',
+            '8' => '⁸',
+            _ => '⁹',
+        })
+        .collect()
+}
+
+/// Linkify ends a URL at `|`, so `https://example.com/a||b` would leave `||b` to the spoiler
+/// syntax. Extends the URL over `|` runs that are followed by more of it, a run followed by
+/// whitespace or the end of the input closes a spoiler around the URL instead.
+fn extend_over_pipes(input: &str, mut end: usize) -> usize {
+    loop {
+        let rest = &input[end..];
+        let after = rest.trim_start_matches('|');
+        if after.len() == rest.len() {
+            return end;
+        }
+        let len = after
+            .find(|c: char| c.is_whitespace() || matches!(c, '|' | '"' | '<' | '>' | '`'))
+            .unwrap_or(after.len());
+        let part = after[..len].trim_end_matches(['.', ',', ':', ';', '!', '?', '\'']);
+        if part.is_empty() {
+            return end;
+        }
+        end += rest.len() - after.len() + part.len();
+        if part.len() < len {
+            return end;
+        }
+    }
+}
+
+/// Whether `start` is the first content on its line, ignoring blockquote and list markers.
+fn at_line_start(source: &str, start: usize) -> bool {
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let prefix = &source[line_start..start];
+
+    (prefix.is_empty() || prefix.ends_with(char::is_whitespace))
+        && prefix.split_whitespace().all(|marker| {
+            marker.chars().all(|c| c == '>')
+                || matches!(marker, "-" | "*" | "+")
+                || marker
+                    .strip_suffix(['.', ')'])
+                    .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+}
+
+/// A `-# ` subtext line. `color` is the text color of the block it is in.
+#[derive(Copy, Clone)]
+struct Subtext {
+    color: Option<[u8; 4]>,
+}
+
+/// Shrinks and dims `style` on a subtext line. Spans with a color of their own, like links, keep
+/// it.
+fn subtext_style(
+    mut style: TextStyle,
+    subtext: Option<Subtext>,
+    opts: &MarkdownOptions,
+) -> TextStyle {
+    if let Some(subtext) = subtext {
+        style.font_size = Some(NotNan::new(opts.subtext_font_size).unwrap());
+        if style.color == subtext.color {
+            style.color = Some(opts.subtext_color);
+        }
+    }
+
+    style
+}
+
+/// Nesting depths are exported as a `u8`, deeper nesting is shown at the deepest level.
+fn clamp_depth(depth: usize) -> u8 {
+    u8::try_from(depth).unwrap_or(u8::MAX)
+}
+
+/// Pairs up the `==` and `||` delimiters from `events[first]` to the end of its block, keyed by
+/// event position and the delimiter's index in that event. An opener pairs with the next delimiter
+/// of its kind that can close it, openers left without one are shown as written. Text in links,
+/// images and code blocks never toggles a span.
+fn pair_delimiters(events: &[(Event, Range<usize>)], first: usize) -> HashSet<(usize, usize)> {
+    let mut pairs = HashSet::new();
+    let mut highlight = None;
+    let mut spoiler = None;
+    let mut literal = 0;
+
+    for (position, (event, _)) in events.iter().enumerate().skip(first) {
+        match event {
+            Event::Start(tag) if is_inline_container(tag) => break,
+            Event::End(tag) if ends_inline_container(tag) => break,
+            Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => literal += 1,
+            Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => literal -= 1,
+            Event::Text(text) if literal == 0 => {
+                let delimiters =
+                    inline::tokenize(text)
+                        .into_iter()
+                        .filter_map(|token| match token {
+                            Inline::Delimiter {
+                                kind,
+                                can_open,
+                                can_close,
+                            } => Some((kind, can_open, can_close)),
+                            _ => None,
+                        });
+
+                for (index, (kind, can_open, can_close)) in delimiters.enumerate() {
+                    let opener = match kind {
+                        DelimiterKind::Highlight => &mut highlight,
+                        DelimiterKind::Spoiler => &mut spoiler,
+                    };
+
+                    match *opener {
+                        Some(open) if can_close => {
+                            pairs.insert(open);
+                            pairs.insert((position, index));
+                            *opener = None;
+                        }
+                        None if can_open => *opener = Some((position, index)),
+                        _ => {}
+                    }
                 }
             }
             _ => {}
         }
     }
 
-    segments
+    pairs
+}
+
+/// Blocks whose text is laid out inline, inline delimiters never span across them.
+fn is_inline_container(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::Item
+            | Tag::TableCell
+            | Tag::DefinitionListTitle
+            | Tag::DefinitionListDefinition
+    )
+}
+
+fn ends_inline_container(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::Item
+            | TagEnd::TableCell
+            | TagEnd::DefinitionListTitle
+            | TagEnd::DefinitionListDefinition
+    )
+}
+
+/// Splits an Obsidian style `|width` or `|widthxheight` size suffix off an image alt text.
+fn split_image_size(alt: &str) -> (&str, Option<u32>, Option<u32>) {
+    let Some((text, size)) = alt.rsplit_once('|') else {
+        return (alt, None, None);
+    };
+
+    let positive = |n: &str| n.parse::<u32>().ok().filter(|&n| n > 0);
+    let (width, height) = match size.trim().split_once('x') {
+        Some((width, height)) => match (positive(width), positive(height)) {
+            (Some(width), Some(height)) => (width, Some(height)),
+            _ => return (alt, None, None),
+        },
+        None => match positive(size.trim()) {
+            Some(width) => (width, None),
+            None => return (alt, None, None),
+        },
+    };
+
+    (text.trim_end(), Some(width), height)
+}
+
+enum HtmlToken<'a> {
+    Text(&'a str),
+    Tag {
+        name: &'a str,
+        closing: bool,
+        raw: &'a str,
+    },
+}
+
+/// Splits raw HTML into tags and the text between them. Anything that doesn't look like a tag
+/// (`<3`, `a < b`) is kept as text.
+fn html_tokens(html: &str) -> Vec<HtmlToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        pos = start + 1;
+
+        let Some(len) = html[start..].find('>') else {
+            break;
+        };
+        let raw = &html[start..start + len + 1];
+        let inner = raw[1..raw.len() - 1].trim_end_matches('/');
+        let closing = inner.starts_with('/');
+        let name = inner
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+
+        let is_tag = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !is_tag {
+            continue;
+        }
+
+        if start > text_start {
+            tokens.push(HtmlToken::Text(&html[text_start..start]));
+        }
+        tokens.push(HtmlToken::Tag { name, closing, raw });
+        pos = start + raw.len();
+        text_start = pos;
+    }
+
+    if text_start < html.len() {
+        tokens.push(HtmlToken::Text(&html[text_start..]));
+    }
+
+    tokens
+}
+
+/// Applies an allowed HTML tag to `style`, returns `None` for tags that are not allowed.
+fn html_tag_style(mut style: TextStyle, name: &str, opts: &MarkdownOptions) -> Option<TextStyle> {
+    match name.to_ascii_lowercase().as_str() {
+        "u" => {
+            style.decoration = Some(TextDecoration::Underline);
+        }
+        "kbd" => {
+            style.font_families = Some(vec![opts.code_block_font_family.clone()]);
+            style.background_color = Some(opts.inline_code_background_color);
+        }
+        "summary" => {
+            if style.font_style.is_none() {
+                style.font_style = Some(FontStyle::default());
+            }
+            style.font_style.as_mut().unwrap().weight = FontWeight::Bold;
+        }
+        "details" => {}
+        _ => return None,
+    }
+
+    Some(style)
+}
+
+/// Some blocks (indented code, thematic breaks) own their trailing newline, others stop right
+/// before it. Returns the end without that newline so it is counted as a line break.
+fn block_content_end(source: &str, end: usize) -> usize {
+    if source[..end].ends_with('\n') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// Maps an offset in the linkified input back to the input that was passed in.
+fn source_offset(inserted_offsets: &[usize], offset: usize) -> usize {
+    offset - inserted_offsets.partition_point(|&inserted| inserted < offset)
+}
+
+/// Turns the newlines between the previous content and `start` into pending line breaks, so
+/// blank lines between blocks survive the same way they were typed.
+fn carry_line_breaks(
+    source: &str,
+    last_content_end: &mut Option<usize>,
+    start: usize,
+    pending_breaks: &mut String,
+) {
+    match *last_content_end {
+        Some(end) if start > end => {
+            for _ in source[end..start].matches('\n') {
+                pending_breaks.push('\n');
+            }
+            *last_content_end = Some(start);
+        }
+        Some(_) => {}
+        None => *last_content_end = Some(start),
+    }
 }
 
 fn highlight_code_block(
@@ -564,7 +2525,7 @@ fn highlight_code_block(
                 segments.push(TextSegment {
                     content: text.to_string(),
                     style: text_style,
-                    href: None,
+                    ..Default::default()
                 });
             }
         }
@@ -733,6 +2694,14 @@ fn get_style(
         obj.set(get_prop_name(rt, "color"), &get_color(rt, *color), rt);
     }
 
+    if let Some(background_color) = &style.background_color {
+        obj.set(
+            get_prop_name(rt, "backgroundColor"),
+            &get_color(rt, *background_color),
+            rt,
+        );
+    }
+
     if opts.height_multiplier != 1.0 {
         obj.set(
             get_prop_name(rt, "heightMultiplier"),
@@ -764,6 +2733,177 @@ fn textsegment_to_jsi_value(
         object.set(get_prop_name(rt, "href"), &get_string(rt, href), rt);
     }
 
+    if segment.kind != SegmentKind::Text {
+        object.set(
+            get_prop_name(rt, "kind"),
+            &get_string(rt, segment.kind.as_str()),
+            rt,
+        );
+    }
+
+    if let SegmentKind::Table(table) = &segment.kind {
+        object.set(
+            get_prop_name(rt, "table"),
+            &table_to_jsi_value(rt, table, opts),
+            rt,
+        );
+    }
+
+    if let SegmentKind::Image {
+        url,
+        title,
+        width,
+        height,
+    } = &segment.kind
+    {
+        object.set(get_prop_name(rt, "url"), &get_string(rt, url), rt);
+
+        if !title.is_empty() {
+            object.set(get_prop_name(rt, "title"), &get_string(rt, title), rt);
+        }
+
+        if let Some(width) = width {
+            object.set(
+                get_prop_name(rt, "width"),
+                &JsiValue::new_number(*width as f64),
+                rt,
+            );
+        }
+
+        if let Some(height) = height {
+            object.set(
+                get_prop_name(rt, "height"),
+                &JsiValue::new_number(*height as f64),
+                rt,
+            );
+        }
+    }
+
+    if let SegmentKind::FootnoteReference { label, index }
+    | SegmentKind::FootnoteDefinition { label, index } = &segment.kind
+    {
+        object.set(get_prop_name(rt, "label"), &get_string(rt, label), rt);
+        object.set(
+            get_prop_name(rt, "index"),
+            &JsiValue::new_number(*index as f64),
+            rt,
+        );
+    }
+
+    if let SegmentKind::Rule { thickness, color } = &segment.kind {
+        object.set(
+            get_prop_name(rt, "thickness"),
+            &JsiValue::new_number(**thickness),
+            rt,
+        );
+        object.set(get_prop_name(rt, "color"), &get_color(rt, *color), rt);
+    }
+
+    if let SegmentKind::Math { tex, display } = &segment.kind {
+        object.set(get_prop_name(rt, "tex"), &get_string(rt, tex), rt);
+        object.set(
+            get_prop_name(rt, "display"),
+            &JsiValue::new_bool(*display),
+            rt,
+        );
+    }
+
+    if let SegmentKind::Mention { kind, id } = &segment.kind {
+        object.set(
+            get_prop_name(rt, "mentionKind"),
+            &get_string(rt, kind.as_str()),
+            rt,
+        );
+        object.set(get_prop_name(rt, "id"), &get_string(rt, id), rt);
+    }
+
+    if let SegmentKind::Timestamp { epoch, style } = segment.kind {
+        object.set(
+            get_prop_name(rt, "epoch"),
+            &JsiValue::new_number(epoch as f64),
+            rt,
+        );
+        object.set(
+            get_prop_name(rt, "format"),
+            &get_string(rt, style.encode_utf8(&mut [0; 4])),
+            rt,
+        );
+    }
+
+    if let SegmentKind::CustomEmoji {
+        id,
+        name,
+        animated,
+        size,
+    } = &segment.kind
+    {
+        object.set(get_prop_name(rt, "id"), &get_string(rt, id), rt);
+        object.set(get_prop_name(rt, "name"), &get_string(rt, name), rt);
+        object.set(
+            get_prop_name(rt, "animated"),
+            &JsiValue::new_bool(*animated),
+            rt,
+        );
+        object.set(get_prop_name(rt, "size"), &JsiValue::new_number(**size), rt);
+    }
+
+    if let SegmentKind::Checkbox { checked, offset } = segment.kind {
+        object.set(
+            get_prop_name(rt, "checked"),
+            &JsiValue::new_bool(checked),
+            rt,
+        );
+        object.set(
+            get_prop_name(rt, "offset"),
+            &JsiValue::new_number(offset as f64),
+            rt,
+        );
+    }
+
+    if segment.quote_depth > 0 {
+        object.set(
+            get_prop_name(rt, "quoteDepth"),
+            &JsiValue::new_number(segment.quote_depth as f64),
+            rt,
+        );
+    }
+
+    if let Some(callout) = segment.callout {
+        object.set(
+            get_prop_name(rt, "callout"),
+            &get_string(rt, callout.as_str()),
+            rt,
+        );
+    }
+
+    if let Some(anchor) = &segment.anchor {
+        object.set(get_prop_name(rt, "anchor"), &get_string(rt, anchor), rt);
+    }
+
+    if let Some(jump) = segment.jump {
+        object.set(
+            get_prop_name(rt, "jump"),
+            &JsiValue::new_number(jump as f64),
+            rt,
+        );
+    }
+
+    if let Some(spoiler) = segment.spoiler {
+        object.set(
+            get_prop_name(rt, "spoiler"),
+            &JsiValue::new_number(spoiler as f64),
+            rt,
+        );
+    }
+
+    if segment.indent > 0 {
+        object.set(
+            get_prop_name(rt, "indent"),
+            &JsiValue::new_number(segment.indent as f64),
+            rt,
+        );
+    }
+
     object.set(
         get_prop_name(rt, "style"),
         &get_style(rt, &segment.style, opts),
@@ -774,3 +2914,90 @@ fn textsegment_to_jsi_value(
 
     jsi_value
 }
+
+fn heading_to_jsi_value(rt: &mut RuntimeHandle<'static>, heading: &Heading) -> JsiValue<'static> {
+    let mut object = JsiObject::new(rt);
+
+    object.set(
+        get_prop_name(rt, "level"),
+        &JsiValue::new_number(heading.level as f64),
+        rt,
+    );
+    object.set(
+        get_prop_name(rt, "text"),
+        &get_string(rt, &heading.text),
+        rt,
+    );
+    object.set(
+        get_prop_name(rt, "anchor"),
+        &get_string(rt, &heading.anchor),
+        rt,
+    );
+    object.set(
+        get_prop_name(rt, "index"),
+        &JsiValue::new_number(heading.index as f64),
+        rt,
+    );
+
+    object.as_value(rt)
+}
+
+fn table_to_jsi_value(
+    rt: &mut RuntimeHandle<'static>,
+    table: &TableBlock,
+    opts: &MarkdownOptions,
+) -> JsiValue<'static> {
+    let mut object = JsiObject::new(rt);
+
+    let alignments = JsiArray::new(table.alignments.len(), rt).as_value(rt);
+    let mut alignments_obj =
+        JsiObject::from_value(&alignments, rt).unwrap_or_else(|| JsiObject::new(rt));
+    for (i, alignment) in table.alignments.iter().enumerate() {
+        alignments_obj.set(
+            get_prop_name(rt, &i.to_string()),
+            &get_string(rt, alignment.as_str()),
+            rt,
+        );
+    }
+    object.set(get_prop_name(rt, "alignments"), &alignments, rt);
+
+    let rows = JsiArray::new(table.rows.len(), rt).as_value(rt);
+    let mut rows_obj = JsiObject::from_value(&rows, rt).unwrap_or_else(|| JsiObject::new(rt));
+    for (i, row) in table.rows.iter().enumerate() {
+        let mut row_obj = JsiObject::new(rt);
+        row_obj.set(
+            get_prop_name(rt, "header"),
+            &JsiValue::new_bool(row.header),
+            rt,
+        );
+
+        let cells = JsiArray::new(row.cells.len(), rt).as_value(rt);
+        let mut cells_obj = JsiObject::from_value(&cells, rt).unwrap_or_else(|| JsiObject::new(rt));
+        for (j, cell) in row.cells.iter().enumerate() {
+            let cell_value = segments_to_jsi_value(rt, cell, opts);
+            cells_obj.set(get_prop_name(rt, &j.to_string()), &cell_value, rt);
+        }
+        row_obj.set(get_prop_name(rt, "cells"), &cells, rt);
+
+        rows_obj.set(get_prop_name(rt, &i.to_string()), &row_obj.as_value(rt), rt);
+    }
+    object.set(get_prop_name(rt, "rows"), &rows, rt);
+
+    object.as_value(rt)
+}
+
+fn segments_to_jsi_value(
+    rt: &mut RuntimeHandle<'static>,
+    segments: &[TextSegment],
+    opts: &MarkdownOptions,
+) -> JsiValue<'static> {
+    let arr = JsiArray::new(segments.len(), rt).as_value(rt);
+    let mut obj = JsiObject::from_value(&arr, rt).unwrap_or_else(|| JsiObject::new(rt));
+
+    for (i, segment) in segments.iter().enumerate() {
+        let val = textsegment_to_jsi_value(rt, segment, opts);
+        obj.set(get_prop_name(rt, &i.to_string()), &val, rt);
+    }
+
+    arr
+}
@@ -0,0 +1,360 @@
+/// Converts a TeX math expression into plain Unicode text, e.g. `\alpha^2 \leq \sum_{i} x_i`
+/// becomes `α² ≤ ∑ᵢ xᵢ`. Commands without a Unicode equivalent are kept as they were typed.
+pub fn tex_to_unicode(tex: &str) -> String {
+    convert(tex, 0)
+}
+
+/// Arguments nested deeper than this are kept as they were typed, so the recursion stays bounded.
+const MAX_DEPTH: usize = 32;
+
+fn convert(tex: &str, depth: usize) -> String {
+    if depth > MAX_DEPTH {
+        return tex.to_string();
+    }
+
+    let mut out = String::with_capacity(tex.len());
+    let mut rest = tex;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\\' => {
+                let name_len = rest[1..]
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len() - 1);
+
+                if name_len == 0 {
+                    // Control symbols: spacing, escaped braces and the like.
+                    let Some(symbol) = rest[1..].chars().next() else {
+                        out.push('\\');
+                        break;
+                    };
+                    match symbol {
+                        ',' | ':' | ';' | ' ' => out.push(' '),
+                        '!' => {}
+                        '\\' => out.push('\n'),
+                        _ => out.push(symbol),
+                    }
+                    rest = &rest[1 + symbol.len_utf8()..];
+                    continue;
+                }
+
+                let name = &rest[1..1 + name_len];
+                rest = &rest[1 + name_len..];
+
+                match name {
+                    "frac" => {
+                        let (numerator, after) = take_argument(rest);
+                        let (denominator, after) = take_argument(after);
+                        out.push_str(&group(convert(numerator, depth + 1)));
+                        out.push('/');
+                        out.push_str(&group(convert(denominator, depth + 1)));
+                        rest = after;
+                    }
+                    "sqrt" => {
+                        let (radicand, after) = take_argument(rest);
+                        out.push('√');
+                        out.push_str(&group(convert(radicand, depth + 1)));
+                        rest = after;
+                    }
+                    "text" | "mathrm" | "mathbf" | "mathit" | "mathsf" | "mathtt"
+                    | "operatorname" => {
+                        let (text, after) = take_argument(rest);
+                        out.push_str(&convert(text, depth + 1));
+                        rest = after;
+                    }
+                    "left" | "right" => {}
+                    _ => match symbol(name) {
+                        Some(symbol) => out.push_str(symbol),
+                        None => {
+                            out.push('\\');
+                            out.push_str(name);
+                        }
+                    },
+                }
+            }
+            '^' | '_' => {
+                let (argument, after) = take_argument(&rest[1..]);
+                let argument = convert(argument, depth + 1);
+                let map = if c == '^' { superscript } else { subscript };
+
+                match argument.chars().map(map).collect::<Option<String>>() {
+                    Some(script) if !script.is_empty() => out.push_str(&script),
+                    _ => {
+                        out.push(c);
+                        out.push_str(&group(argument));
+                    }
+                }
+                rest = after;
+            }
+            '{' | '}' => rest = &rest[1..],
+            _ => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    out
+}
+
+/// Splits the next argument (a `{group}`, a `\command` or a single character) off `tex`.
+fn take_argument(tex: &str) -> (&str, &str) {
+    let tex = tex.trim_start();
+
+    match tex.chars().next() {
+        Some('{') => {
+            let mut depth = 0;
+            for (i, c) in tex.char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return (&tex[1..i], &tex[i + 1..]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (&tex[1..], "")
+        }
+        Some('\\') => {
+            let len = match tex[1..].find(|c: char| !c.is_ascii_alphabetic()) {
+                Some(0) => tex[1..].chars().next().map_or(0, char::len_utf8),
+                Some(len) => len,
+                None => tex.len() - 1,
+            };
+            tex.split_at(1 + len)
+        }
+        Some(c) => tex.split_at(c.len_utf8()),
+        None => ("", ""),
+    }
+}
+
+/// Wraps multi-character expressions in parentheses so `\frac{a+b}{2}` reads `(a+b)/2`.
+fn group(text: String) -> String {
+    if text.chars().count() > 1 {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' | '−' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        '′' | '*' | '∘' => c,
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' | '−' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "oint" => "∮",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "infty" => "∞",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "ll" => "≪",
+        "gg" => "≫",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "pm" => "±",
+        "mp" => "∓",
+        "times" => "×",
+        "div" => "÷",
+        "cdot" => "·",
+        "ast" => "∗",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "supset" => "⊃",
+        "subseteq" => "⊆",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "emptyset" | "varnothing" => "∅",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "angle" => "∠",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" => "∣",
+        "prime" => "′",
+        "degree" => "°",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "quad" => " ",
+        "qquad" => "  ",
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "log" => "log",
+        "ln" => "ln",
+        "exp" => "exp",
+        "lim" => "lim",
+        "max" => "max",
+        "min" => "min",
+        _ => return None,
+    })
+}
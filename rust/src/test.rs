@@ -5,25 +5,40 @@ use serde_json::json;
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Once;
+
     use linkify::LinkFinder;
+    use ordered_float::NotNan;
 
-    use crate::{parse_markdown, MarkdownOptions, LINKIFY};
+    use crate::{
+        parse_markdown, parse_outline, timestamp, CalloutKind, ColumnAlignment, FontSlant,
+        FontWeight, HtmlPolicy, MarkdownDialect, MarkdownOptions, MentionKind, MentionTarget,
+        Mentions, SegmentKind, TextDecoration, TextSegment, LINKIFY,
+    };
 
     use super::*;
 
+    static INIT: Once = Once::new();
+
+    fn init_linkify() {
+        INIT.call_once(|| {
+            let mut linkify = LinkFinder::new();
+            linkify.url_can_be_iri(false);
+            linkify.url_must_have_scheme(true);
+            linkify.kinds(&[linkify::LinkKind::Url]);
+            unsafe {
+                LINKIFY.as_mut_ptr().write(linkify);
+            };
+        });
+    }
+
     #[test]
     fn test_markdown_parsing() -> Result<(), Box<dyn std::error::Error>> {
         let path = std::path::Path::new("../TEST.md");
 
         let file = fs::read_to_string(path)?;
 
-        let mut linkify = LinkFinder::new();
-        linkify.url_can_be_iri(false);
-        linkify.url_must_have_scheme(true);
-        linkify.kinds(&[linkify::LinkKind::Url]);
-        unsafe {
-            LINKIFY.as_mut_ptr().write(linkify);
-        };
+        init_linkify();
 
         let mut width = 0;
 
@@ -47,4 +62,949 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_inline_code() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("run `cargo build` now", &opts);
+
+        let code = result
+            .iter()
+            .find(|segment| segment.content == "cargo build")
+            .expect("inline code segment");
+
+        assert_eq!(
+            code.style.font_families,
+            Some(vec![opts.code_block_font_family.clone()])
+        );
+        assert_eq!(
+            code.style.background_color,
+            Some(opts.inline_code_background_color)
+        );
+        assert_eq!(result.last().unwrap().content, " now");
+    }
+
+    #[test]
+    fn test_indented_code_block() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let input = "trace:\n\n    at foo (a.rs:1)\n      at bar (b.rs:2)\n\ndone";
+        let result = parse_markdown(input, &opts);
+
+        let text = result
+            .iter()
+            .map(|segment| segment.content.as_str())
+            .collect::<String>();
+        assert_eq!(text, "trace:\n\nat foo (a.rs:1)\n  at bar (b.rs:2)\n\ndone");
+
+        let code = result
+            .iter()
+            .find(|segment| segment.content.contains("at bar"))
+            .expect("code segment");
+        assert_eq!(
+            code.style.font_families,
+            Some(vec![opts.code_block_font_family.clone()])
+        );
+    }
+
+    #[test]
+    fn test_heading_line_breaks() {
+        init_linkify();
+
+        for input in ["# text\n\nmore", "text\n==\n\nmore", "text\n--\n\nmore"] {
+            let result = parse_markdown(input, &MarkdownOptions::default());
+            assert_eq!(result.last().unwrap().content, "\n\nmore", "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_lists() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("3. three\n4. four\n   - nested\n\nafter", &opts);
+
+        let markers = result
+            .iter()
+            .filter(|segment| segment.kind == SegmentKind::ListMarker)
+            .map(|segment| (segment.content.as_str(), segment.indent))
+            .collect::<Vec<_>>();
+        assert_eq!(markers, vec![("3. ", 1), ("\n4. ", 1), ("\n◦ ", 2)]);
+
+        let after = result.last().unwrap();
+        assert_eq!(after.content, "\n\nafter");
+        assert_eq!(after.indent, 0);
+
+        let deep = format!("{}deep", "- ".repeat(300));
+        let result = parse_markdown(&deep, &opts);
+        assert_eq!(result.last().unwrap().indent, u8::MAX);
+    }
+
+    #[test]
+    fn test_task_list_offsets() {
+        init_linkify();
+
+        let input = "see https://example.com\n\n- [ ] todo\n- [x] done";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+
+        let checkboxes = result
+            .iter()
+            .filter_map(|segment| match segment.kind {
+                SegmentKind::Checkbox { checked, offset } => Some((checked, offset)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(checkboxes.len(), 2);
+        assert_eq!(&input[checkboxes[0].1..checkboxes[0].1 + 3], "[ ]");
+        assert_eq!(&input[checkboxes[1].1..checkboxes[1].1 + 3], "[x]");
+        assert!(!checkboxes[0].0);
+        assert!(checkboxes[1].0);
+        assert!(!result
+            .iter()
+            .any(|segment| segment.kind == SegmentKind::ListMarker));
+    }
+
+    #[test]
+    fn test_blockquote_depth() {
+        init_linkify();
+
+        let result = parse_markdown("> outer\n>> inner\n\nplain", &MarkdownOptions::default());
+
+        let depths = result
+            .iter()
+            .map(|segment| (segment.content.trim(), segment.quote_depth))
+            .collect::<Vec<_>>();
+        assert_eq!(depths, vec![("outer", 1), ("inner", 2), ("plain", 0)]);
+
+        let deep = format!("{} deep\n\nplain", ">".repeat(300));
+        let result = parse_markdown(&deep, &MarkdownOptions::default());
+        assert_eq!(result[0].quote_depth, u8::MAX);
+        assert_eq!(result.last().unwrap().quote_depth, 0);
+    }
+
+    #[test]
+    fn test_tables() {
+        init_linkify();
+
+        let input = "| name | **qty** |\n|:-----|----:|\n| `a` | 1 |\n| b | 2 |\n\nafter";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+
+        let SegmentKind::Table(table) = &result[0].kind else {
+            panic!("expected a table, got {:?}", result[0]);
+        };
+        assert_eq!(
+            table.alignments,
+            vec![ColumnAlignment::Left, ColumnAlignment::Right]
+        );
+        assert_eq!(table.rows.len(), 3);
+        assert!(table.rows[0].header);
+        assert!(!table.rows[1].header);
+        assert_eq!(table.rows[0].cells[1][0].content, "qty");
+        assert_eq!(table.rows[1].cells[0][0].content, "a");
+        assert_eq!(table.rows[2].cells[1][0].content, "2");
+
+        assert_eq!(result[1].content, "\n\nafter");
+
+        let opts = MarkdownOptions {
+            base_font_size: 20.0,
+            ..Default::default()
+        };
+        let result = parse_markdown(input, &opts);
+        assert_eq!(result[0].style.font_size, Some(NotNan::new(20.0).unwrap()));
+    }
+
+    #[test]
+    fn test_images() {
+        init_linkify();
+
+        let input =
+            "look ![a *cat*|200x100](https://example.com/cat.png \"Cat\") and ![dog](dog.png)";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+
+        let images = result
+            .iter()
+            .filter_map(|segment| match &segment.kind {
+                SegmentKind::Image {
+                    url,
+                    title,
+                    width,
+                    height,
+                } => Some((
+                    segment.content.as_str(),
+                    url.as_str(),
+                    title.as_str(),
+                    *width,
+                    *height,
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            images,
+            vec![
+                (
+                    "a cat",
+                    "https://example.com/cat.png",
+                    "Cat",
+                    Some(200),
+                    Some(100)
+                ),
+                ("dog", "dog.png", "", None, None),
+            ]
+        );
+
+        // Sizes that can't be laid out stay part of the alt text.
+        let result = parse_markdown(
+            "![a|0x](i.png)![b|0](i.png)![c|10x0](i.png)![d|10x](i.png)",
+            &MarkdownOptions::default(),
+        );
+        let images = result
+            .iter()
+            .filter_map(|segment| match &segment.kind {
+                SegmentKind::Image { width, height, .. } => {
+                    Some((segment.content.as_str(), *width, *height))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            images,
+            vec![
+                ("a|0x", None, None),
+                ("b|0", None, None),
+                ("c|10x0", None, None),
+                ("d|10x", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_footnotes() {
+        init_linkify();
+
+        let input = "[^b]: second note\n\nfirst[^a]\n\n[^a]: first note\n\nthen[^b]";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+
+        let contents = result
+            .iter()
+            .map(|segment| segment.content.as_str())
+            .collect::<String>();
+        assert_eq!(contents, "first¹\n\nthen²\n\n¹ first note\n² second note");
+
+        assert!(matches!(
+            &result[1].kind,
+            SegmentKind::FootnoteReference { label, index: 1 } if label == "a"
+        ));
+    }
+
+    #[test]
+    fn test_rules() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("above\n\n---\n\nbelow", &opts);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].content, "above");
+        assert!(matches!(
+            result[1].kind,
+            SegmentKind::Rule { thickness, color }
+                if *thickness == opts.rule_thickness && color == opts.rule_color
+        ));
+        assert_eq!(result[2].content, "\n\nbelow");
+    }
+
+    #[test]
+    fn test_html_policy() {
+        init_linkify();
+
+        let input = "a <u>b</u> <script>c</script> <3 x<sup>2</sup>";
+
+        let render = |html_policy| {
+            let opts = MarkdownOptions {
+                html_policy,
+                ..Default::default()
+            };
+            parse_markdown(input, &opts)
+        };
+
+        let text = |segments: &[TextSegment]| {
+            segments
+                .iter()
+                .map(|segment| segment.content.as_str())
+                .collect::<String>()
+        };
+
+        assert_eq!(text(&render(HtmlPolicy::Escape)), input);
+        assert_eq!(text(&render(HtmlPolicy::Strip)), "a b c <3 x2");
+
+        let allowed = render(HtmlPolicy::Allow);
+        assert_eq!(text(&allowed), "a b <script>c</script> <3 x<sup>2</sup>");
+        let underlined = allowed
+            .iter()
+            .find(|segment| segment.content == "b")
+            .unwrap();
+        assert_eq!(underlined.style.decoration, Some(TextDecoration::Underline));
+    }
+
+    #[test]
+    fn test_math() {
+        init_linkify();
+
+        let input = "so $x^2 + \\alpha_{1} \\leq \\frac{n+1}{2}$ and\n\n$$\\sum_{i} x_i \\rightarrow \\infty$$";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+
+        let math = result
+            .iter()
+            .filter_map(|segment| match &segment.kind {
+                SegmentKind::Math { display, .. } => Some((segment.content.trim(), *display)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            math,
+            vec![("x² + α₁ ≤ (n+1)/2", false), ("∑ᵢ xᵢ → ∞", true)]
+        );
+
+        // Deep nesting is cut off instead of overflowing the stack of a small thread.
+        let nested = format!("${}x{}$", "\\sqrt{".repeat(5000), "}".repeat(5000));
+        let result = std::thread::Builder::new()
+            .stack_size(512 * 1024)
+            .spawn(move || parse_markdown(&nested, &MarkdownOptions::default()))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(result[0].content.starts_with("√(√("));
+        assert!(result[0].content.contains("\\sqrt{\\sqrt{"));
+    }
+
+    #[test]
+    fn test_highlight() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("a ==marked **bold**== b == c\n\n==open", &opts);
+
+        let highlighted = result
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.style.background_color == Some(opts.highlight_color),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            highlighted,
+            vec![
+                ("a ", false),
+                ("marked ", true),
+                ("bold", true),
+                (" b == c", false),
+                ("\n\n==open", false),
+            ]
+        );
+
+        let code = parse_markdown("==a `==` b", &opts);
+        assert!(code
+            .iter()
+            .all(|segment| segment.style.background_color != Some(opts.highlight_color)));
+        assert_eq!(code[0].content, "==a ");
+
+        // Link text, autolinked URLs included, is shown as it was written.
+        let url = parse_markdown("see https://example.com/?a==b&c==d ==[x==y](z)==", &opts);
+        let text = url
+            .iter()
+            .map(|segment| (segment.content.as_str(), segment.href.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            text,
+            vec![
+                ("see ", false),
+                ("https://example.com/?a==b&c==d", true),
+                (" ", false),
+                ("x==y", true),
+            ]
+        );
+        assert_eq!(url[3].style.background_color, Some(opts.highlight_color));
+
+        // Unclosed openers are paired in one pass over the block, not one scan each.
+        let input = "==a *b* ||c ".repeat(20_000);
+        let start = std::time::Instant::now();
+        let unclosed = parse_markdown(&input, &opts);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert!(unclosed.iter().all(|segment| {
+            segment.style.background_color.is_none() && segment.spoiler.is_none()
+        }));
+    }
+
+    #[test]
+    fn test_discord_underline() {
+        init_linkify();
+
+        let opts = MarkdownOptions {
+            dialect: MarkdownDialect::Discord,
+            ..Default::default()
+        };
+        let result = parse_markdown("__under__ **bold** ___both___", &opts);
+
+        let under = &result[0];
+        assert_eq!(under.content, "under");
+        assert_eq!(under.style.decoration, Some(TextDecoration::Underline));
+        assert!(under.style.font_style.is_none());
+
+        let bold = &result[2];
+        assert_eq!(bold.content, "bold");
+        assert_eq!(bold.style.decoration, None);
+        assert_eq!(
+            bold.style.font_style.as_ref().map(|style| style.weight),
+            Some(FontWeight::Bold)
+        );
+
+        let both = &result[4];
+        assert_eq!(both.content, "both");
+        assert_eq!(both.style.decoration, Some(TextDecoration::Underline));
+        assert_eq!(
+            both.style.font_style.as_ref().map(|style| style.slant),
+            Some(FontSlant::Italic)
+        );
+
+        let common = parse_markdown("__bold__", &MarkdownOptions::default());
+        assert_eq!(common[0].style.decoration, None);
+    }
+
+    #[test]
+    fn test_spoilers() {
+        init_linkify();
+
+        let result = parse_markdown(
+            "||a **b**|| c ||d|| || e\n\n||open",
+            &MarkdownOptions::default(),
+        );
+
+        let spoilers = result
+            .iter()
+            .map(|segment| (segment.content.as_str(), segment.spoiler))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            spoilers,
+            vec![
+                ("a ", Some(0)),
+                ("b", Some(0)),
+                (" c ", None),
+                ("d", Some(1)),
+                (" || e", None),
+                ("\n\n||open", None),
+            ]
+        );
+
+        let code = parse_markdown("||a `||` b", &MarkdownOptions::default());
+        assert!(code.iter().all(|segment| segment.spoiler.is_none()));
+        assert_eq!(code[0].content, "||a ");
+
+        let url = parse_markdown(
+            "https://example.com/a||b||c ||https://example.com||",
+            &MarkdownOptions::default(),
+        );
+        let links = url
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.href.is_some(),
+                    segment.spoiler,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                ("https://example.com/a||b||c", true, None),
+                (" ", false, None),
+                ("https://example.com", true, Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subtext() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("-# small **bold**\nnormal\n\n> -# quoted", &opts);
+
+        let sizes = result
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.style.font_size.map(|size| size.into_inner()),
+                    segment.style.color == Some(opts.subtext_color),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sizes,
+            vec![
+                ("small ", Some(14.4), true),
+                ("bold", Some(14.4), true),
+                ("\nnormal", Some(18.0), false),
+                ("\n\nquoted", Some(14.4), true),
+            ]
+        );
+
+        let inline = parse_markdown("a -# b", &opts);
+        assert_eq!(inline[0].content, "a -# b");
+
+        // Spans that started on the line before only shrink from the `-# ` on.
+        for input in ["**a\n-# b**", "[a\n-# b](x)", "~~a\n-# b~~"] {
+            let result = parse_markdown(input, &opts);
+            let sizes = result
+                .iter()
+                .map(|segment| segment.style.font_size.map(|size| size.into_inner()))
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, vec![Some(18.0), Some(14.4)], "{:?}", input);
+        }
+
+        let link = parse_markdown("[a\n-# b](x)", &opts);
+        assert_eq!(link[1].style.color, Some(opts.link_color));
+
+        let next_line = parse_markdown("-# **a\nb**", &opts);
+        assert_eq!(next_line[1].content, "\nb");
+        assert_eq!(
+            next_line[1].style.font_size.map(|size| size.into_inner()),
+            Some(18.0)
+        );
+        assert_eq!(next_line[1].style.color, None);
+    }
+
+    #[test]
+    fn test_user_mentions() {
+        init_linkify();
+
+        let opts = MarkdownOptions {
+            mentions: Mentions {
+                users: [(
+                    "123".to_string(),
+                    MentionTarget {
+                        name: "Alice".to_string(),
+                        color: Some([255, 0, 0, 255]),
+                    },
+                )]
+                .into(),
+                usernames: [
+                    ("alice".to_string(), "123".to_string()),
+                    ("bob".to_string(), "789".to_string()),
+                ]
+                .into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = parse_markdown(
+            "hi <@123>, <@!456> and @bob. @alice, @everyone. mail a@b.com",
+            &opts,
+        );
+
+        let mentions = result
+            .iter()
+            .filter_map(|segment| match &segment.kind {
+                SegmentKind::Mention { kind, id } => {
+                    Some((segment.content.as_str(), *kind, id.as_str()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mentions,
+            vec![
+                ("@Alice", MentionKind::User, "123"),
+                ("@456", MentionKind::User, "456"),
+                ("@bob", MentionKind::User, "789"),
+                ("@Alice", MentionKind::User, "123"),
+            ]
+        );
+        assert_eq!(result[1].style.color, Some([255, 0, 0, 255]));
+        assert_eq!(result[3].style.color, Some(opts.mention_color));
+        assert_eq!(result.last().unwrap().content, ", @everyone. mail a@b.com");
+        assert_eq!(result.last().unwrap().style.background_color, None);
+    }
+
+    #[test]
+    fn test_channel_and_role_mentions() {
+        init_linkify();
+
+        let target = |name: &str, color| MentionTarget {
+            name: name.to_string(),
+            color,
+        };
+        let opts = MarkdownOptions {
+            mentions: Mentions {
+                channels: [("1".to_string(), target("general", None))].into(),
+                roles: [("2".to_string(), target("mods", Some([0, 255, 0, 255])))].into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = parse_markdown("<#1> <@&2> <#3> <@&4>", &opts);
+
+        let mentions = result
+            .iter()
+            .filter_map(|segment| match &segment.kind {
+                SegmentKind::Mention { kind, id } => {
+                    Some((segment.content.as_str(), *kind, id.as_str()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mentions,
+            vec![
+                ("#general", MentionKind::Channel, "1"),
+                ("@mods", MentionKind::Role, "2"),
+                ("#deleted-channel", MentionKind::Channel, "3"),
+                ("@deleted-role", MentionKind::Role, "4"),
+            ]
+        );
+        assert_eq!(result[2].style.color, Some([0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn test_custom_emoji() {
+        init_linkify();
+
+        let result = parse_markdown(
+            "# <:wave:123>\n\nhi <a:party_blob:456>! <:broken:> `<:code:1>`",
+            &MarkdownOptions::default(),
+        );
+
+        let emoji = result
+            .iter()
+            .filter(|segment| matches!(segment.kind, SegmentKind::CustomEmoji { .. }))
+            .map(|segment| (segment.content.as_str(), &segment.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            emoji,
+            vec![
+                (
+                    ":wave:",
+                    &SegmentKind::CustomEmoji {
+                        id: "123".to_string(),
+                        name: "wave".to_string(),
+                        animated: false,
+                        size: NotNan::new(46.8).unwrap(),
+                    }
+                ),
+                (
+                    ":party_blob:",
+                    &SegmentKind::CustomEmoji {
+                        id: "456".to_string(),
+                        name: "party_blob".to_string(),
+                        animated: true,
+                        size: NotNan::new(18.0).unwrap(),
+                    }
+                ),
+            ]
+        );
+        assert!(result
+            .iter()
+            .any(|segment| segment.content == "! <:broken:> "));
+    }
+
+    #[test]
+    fn test_emoji_shortcodes() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown(":wave: hi :) :nope: `:wave:`", &opts);
+        assert_eq!(result[0].content, "👋 hi :) :nope: ");
+        assert_eq!(result[1].content, ":wave:");
+
+        let opts = MarkdownOptions {
+            emoticons_to_emoji: true,
+            ..Default::default()
+        };
+        let result = parse_markdown("hi :) <3, 10:30 a:D", &opts);
+        assert_eq!(result[0].content, "hi 🙂 ❤️, 10:30 a:D");
+
+        let opts = MarkdownOptions {
+            shortcodes_to_emoji: false,
+            ..Default::default()
+        };
+        let result = parse_markdown(":+1:", &opts);
+        assert_eq!(result[0].content, ":+1:");
+    }
+
+    #[test]
+    fn test_timestamps() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown(
+            "<t:1700000000> <t:1700000000:d> <t:1700000000:R> <t:1:x>",
+            &opts,
+        );
+
+        assert_eq!(result[0].content, "November 14, 2023 10:13 PM");
+        assert_eq!(
+            result[0].kind,
+            SegmentKind::Timestamp {
+                epoch: 1700000000,
+                style: 'f'
+            }
+        );
+        assert_eq!(result[2].content, "11/14/2023");
+        assert!(result[4].content.ends_with(" ago"));
+        assert_eq!(
+            result[4].kind,
+            SegmentKind::Timestamp {
+                epoch: 1700000000,
+                style: 'R'
+            }
+        );
+        assert_eq!(result[5].content, " <t:1:x>");
+
+        let opts = MarkdownOptions {
+            timezone_offset: 120,
+            locale: "de-DE".into(),
+            ..Default::default()
+        };
+        let result = parse_markdown("<t:1700000000:F>", &opts);
+        assert_eq!(result[0].content, "Mittwoch, 15. November 2023 00:13");
+
+        // Epochs out of the range of JS `Date` stay as text.
+        let input = "<t:-9223372036854775808:R> <t:9223372036854775807> <t:8640000000001:d>";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].content, input);
+
+        let result = parse_markdown("<t:8640000000000:d>", &MarkdownOptions::default());
+        assert_eq!(result[0].content, "9/13/275760");
+
+        assert_eq!(timestamp::format(i64::MAX, 'f', 60, "en-US", 0), None);
+        assert_eq!(timestamp::format(i64::MIN, 'R', 0, "en-US", 1), None);
+    }
+
+    #[test]
+    fn test_heading_anchors() {
+        init_linkify();
+
+        let result = parse_markdown(
+            "# Getting *Started*!\n\n## Setup {#install}\n\n## Getting Started\n\n[see setup](#install) [top](#getting-started) [nowhere](#nope)",
+            &MarkdownOptions::default(),
+        );
+
+        let anchors = result
+            .iter()
+            .filter_map(|segment| Some((segment.content.trim(), segment.anchor.as_deref()?)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            anchors,
+            vec![
+                ("Getting", "getting-started"),
+                ("Started", "getting-started"),
+                ("!", "getting-started"),
+                ("Setup", "install"),
+                ("Getting Started", "getting-started-1"),
+            ]
+        );
+
+        let jumps = result
+            .iter()
+            .filter(|segment| segment.href.is_some())
+            .map(|segment| (segment.content.trim(), segment.jump))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            jumps,
+            vec![("see setup", Some(1)), ("top", Some(0)), ("nowhere", None)]
+        );
+
+        // Footnote definitions move to the end, the jump follows their heading there.
+        let input = "[foot](#foot) x [^1]\n\n[^1]: # Foot\n\n# Real";
+        let result = parse_markdown(input, &MarkdownOptions::default());
+        let outline = parse_outline(input, &MarkdownOptions::default());
+        let jump = result[0].jump.expect("jump to the footnote heading");
+        assert_eq!(outline[jump].anchor, "foot");
+        assert_eq!(result[outline[jump].index].content, "Foot");
+    }
+
+    #[test]
+    fn test_outline() {
+        init_linkify();
+
+        let input = "# Title\n\nintro\n\n## First **part**\n\n### Deep {#deep}\n\n## Title";
+        let opts = MarkdownOptions::default();
+        let outline = parse_outline(input, &opts);
+
+        let entries = outline
+            .iter()
+            .map(|heading| {
+                (
+                    heading.level,
+                    heading.text.as_str(),
+                    heading.anchor.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                (1, "Title", "title"),
+                (2, "First part", "first-part"),
+                (3, "Deep", "deep"),
+                (2, "Title", "title-1"),
+            ]
+        );
+
+        let segments = parse_markdown(input, &opts);
+        for heading in &outline {
+            assert_eq!(
+                segments[heading.index].anchor.as_ref(),
+                Some(&heading.anchor)
+            );
+        }
+        assert_eq!(outline[1].index, 2);
+
+        // Headings of footnote definitions are listed where the definitions are rendered.
+        let input = "x [^1]\n\n[^1]: # Foot\n\n# Real";
+        let outline = parse_outline(input, &opts);
+        let segments = parse_markdown(input, &opts);
+        let entries = outline
+            .iter()
+            .map(|heading| {
+                (
+                    heading.text.as_str(),
+                    segments[heading.index].content.trim(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![("Real", "Real"), ("Foot", "Foot")]);
+    }
+
+    #[test]
+    fn test_definition_lists() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("Term\n: first\n: second\n\nafter", &opts);
+
+        let definitions = result
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.style.font_style.as_ref().map(|style| style.weight),
+                    segment.indent,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            definitions,
+            vec![
+                ("Term", Some(FontWeight::Bold), 0),
+                ("\nfirst", None, 1),
+                ("\nsecond", None, 1),
+                ("\n\nafter", None, 0),
+            ]
+        );
+
+        let quoted = parse_markdown("> term\n> : definition\n\nnext", &opts);
+        let quoted = quoted
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.style.font_style.as_ref().map(|style| style.weight),
+                    segment.indent,
+                    segment.quote_depth,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            quoted,
+            vec![
+                ("term", Some(FontWeight::Bold), 0, 1),
+                ("\ndefinition", None, 1, 1),
+                ("\n\nnext", None, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_callouts() {
+        init_linkify();
+
+        let opts = MarkdownOptions::default();
+        let result = parse_markdown("intro\n\n> [!WARNING]\n> Careful\n\n> plain", &opts);
+
+        let callouts = result
+            .iter()
+            .map(|segment| {
+                (
+                    segment.content.as_str(),
+                    segment.kind == SegmentKind::CalloutTitle,
+                    segment.callout,
+                    segment.quote_depth,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            callouts,
+            vec![
+                ("intro", false, None, 0),
+                ("\n\nWarning", true, Some(CalloutKind::Warning), 1),
+                ("\nCareful", false, Some(CalloutKind::Warning), 1),
+                ("\n\nplain", false, None, 1),
+            ]
+        );
+        assert_eq!(result[1].style.color, Some(opts.callout_warning_color));
+    }
+
+    #[test]
+    fn test_wiki_links() {
+        init_linkify();
+
+        let opts = MarkdownOptions {
+            wiki_link_template: "app://wiki/{slug}".into(),
+            ..Default::default()
+        };
+        let result = parse_markdown(
+            "see [[Getting Started|the guide]], [[FAQ]] and [[]] or `[[code]]`, [[|x]] [[Setup|]]",
+            &opts,
+        );
+
+        let links = result
+            .iter()
+            .filter_map(|segment| Some((segment.content.as_str(), segment.href.as_deref()?)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            links,
+            vec![
+                ("the guide", "app://wiki/getting-started"),
+                ("FAQ", "app://wiki/faq"),
+                ("Setup", "app://wiki/setup"),
+            ]
+        );
+        assert_eq!(result[1].style.decoration, Some(TextDecoration::Underline));
+        assert_eq!(result[4].content, " and [[]] or ");
+        assert_eq!(result[6].content, ", [[|x]] ");
+    }
 }
@@ -0,0 +1,294 @@
+//! Formatting for `<t:unix:style>` timestamps. Only a handful of locales are bundled, anything
+//! else falls back to US English.
+
+/// The styles a timestamp can be written in, `f` is used when none is given.
+pub const STYLES: &[char] = &['t', 'T', 'd', 'D', 'f', 'F', 'R'];
+
+/// The largest epoch JS `Date` can represent, in seconds on either side of 1970.
+pub const MAX_EPOCH: i64 = 8_640_000_000_000;
+
+struct Locale {
+    months: [&'static str; 12],
+    weekdays: [&'static str; 7],
+    hour12: bool,
+    short_date: &'static str,
+    long_date: &'static str,
+    weekday_date: &'static str,
+    past: &'static str,
+    future: &'static str,
+    /// Singular and plural of seconds, minutes, hours, days, months and years.
+    units: [(&'static str, &'static str); 6],
+}
+
+const EN_US: Locale = Locale {
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    weekdays: [
+        "Sunday",
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+    ],
+    hour12: true,
+    short_date: "{M}/{D}/{Y}",
+    long_date: "{month} {D}, {Y}",
+    weekday_date: "{weekday}, {date}",
+    past: "{} ago",
+    future: "in {}",
+    units: [
+        ("second", "seconds"),
+        ("minute", "minutes"),
+        ("hour", "hours"),
+        ("day", "days"),
+        ("month", "months"),
+        ("year", "years"),
+    ],
+};
+
+const EN_GB: Locale = Locale {
+    hour12: false,
+    short_date: "{DD}/{MM}/{Y}",
+    long_date: "{D} {month} {Y}",
+    ..EN_US
+};
+
+const DE: Locale = Locale {
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    weekdays: [
+        "Sonntag",
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+    ],
+    hour12: false,
+    short_date: "{DD}.{MM}.{Y}",
+    long_date: "{D}. {month} {Y}",
+    weekday_date: "{weekday}, {date}",
+    past: "vor {}",
+    future: "in {}",
+    units: [
+        ("Sekunde", "Sekunden"),
+        ("Minute", "Minuten"),
+        ("Stunde", "Stunden"),
+        ("Tag", "Tagen"),
+        ("Monat", "Monaten"),
+        ("Jahr", "Jahren"),
+    ],
+};
+
+const FR: Locale = Locale {
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    weekdays: [
+        "dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+    ],
+    hour12: false,
+    short_date: "{DD}/{MM}/{Y}",
+    long_date: "{D} {month} {Y}",
+    weekday_date: "{weekday} {date}",
+    past: "il y a {}",
+    future: "dans {}",
+    units: [
+        ("seconde", "secondes"),
+        ("minute", "minutes"),
+        ("heure", "heures"),
+        ("jour", "jours"),
+        ("mois", "mois"),
+        ("an", "ans"),
+    ],
+};
+
+const ES: Locale = Locale {
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    weekdays: [
+        "domingo",
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+    ],
+    hour12: false,
+    short_date: "{D}/{M}/{Y}",
+    long_date: "{D} de {month} de {Y}",
+    weekday_date: "{weekday}, {date}",
+    past: "hace {}",
+    future: "dentro de {}",
+    units: [
+        ("segundo", "segundos"),
+        ("minuto", "minutos"),
+        ("hora", "horas"),
+        ("día", "días"),
+        ("mes", "meses"),
+        ("año", "años"),
+    ],
+};
+
+fn locale(tag: &str) -> &'static Locale {
+    let mut parts = tag.split(['-', '_']);
+    let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+    let region = parts.next().unwrap_or_default().to_ascii_uppercase();
+
+    match (language.as_str(), region.as_str()) {
+        ("en", "GB" | "IE" | "AU" | "NZ") => &EN_GB,
+        ("de", _) => &DE,
+        ("fr", _) => &FR,
+        ("es", _) => &ES,
+        _ => &EN_US,
+    }
+}
+
+/// Formats `epoch` (seconds) in one of the [`STYLES`]. `offset` is the timezone offset from UTC
+/// in minutes and `now` the current time, used for relative timestamps. `None` when the shifted
+/// time doesn't fit an `i64`.
+pub fn format(epoch: i64, style: char, offset: i32, locale_tag: &str, now: i64) -> Option<String> {
+    let locale = locale(locale_tag);
+
+    if style == 'R' {
+        return Some(relative(epoch.checked_sub(now)?, locale));
+    }
+
+    let local = epoch.checked_add(i64::from(offset) * 60)?;
+    let days = local.div_euclid(86400);
+    let seconds = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let weekday = (days + 4).rem_euclid(7) as usize;
+
+    let date = |pattern: &str| {
+        pattern
+            .replace("{weekday}", locale.weekdays[weekday])
+            .replace("{date}", locale.long_date)
+            .replace("{month}", locale.months[month as usize - 1])
+            .replace("{DD}", &format!("{:02}", day))
+            .replace("{D}", &day.to_string())
+            .replace("{MM}", &format!("{:02}", month))
+            .replace("{M}", &month.to_string())
+            .replace("{Y}", &year.to_string())
+    };
+    let time = |with_seconds: bool| {
+        let (hour, minute, second) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+        let seconds = if with_seconds {
+            format!(":{:02}", second)
+        } else {
+            String::new()
+        };
+
+        if locale.hour12 {
+            let period = if hour < 12 { "AM" } else { "PM" };
+            let hour = if hour % 12 == 0 { 12 } else { hour % 12 };
+            format!("{}:{:02}{} {}", hour, minute, seconds, period)
+        } else {
+            format!("{:02}:{:02}{}", hour, minute, seconds)
+        }
+    };
+
+    Some(match style {
+        't' => time(false),
+        'T' => time(true),
+        'd' => date(locale.short_date),
+        'D' => date(locale.long_date),
+        'F' => format!("{} {}", date(locale.weekday_date), time(false)),
+        _ => format!("{} {}", date(locale.long_date), time(false)),
+    })
+}
+
+/// "5 minutes ago" or "in 2 days" for a difference of `delta` seconds.
+fn relative(delta: i64, locale: &Locale) -> String {
+    let seconds = delta.unsigned_abs();
+    let (amount, unit) = match seconds {
+        0..60 => (seconds, 0),
+        60..3600 => (seconds / 60, 1),
+        3600..86400 => (seconds / 3600, 2),
+        86400..2592000 => (seconds / 86400, 3),
+        2592000..31536000 => (seconds / 2592000, 4),
+        _ => (seconds / 31536000, 5),
+    };
+
+    let (singular, plural) = locale.units[unit];
+    let amount = format!("{} {}", amount, if amount == 1 { singular } else { plural });
+    let pattern = if delta < 0 {
+        locale.past
+    } else {
+        locale.future
+    };
+
+    pattern.replace("{}", &amount)
+}
+
+/// Converts days since 1970-01-01 into a (year, month, day) date of the proleptic Gregorian
+/// calendar, following Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}